@@ -0,0 +1,23 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Finds the version of tree-sitter-lua in `Cargo.lock` for `--version`, since Cargo only
+//! tells a crate its own version.
+
+fn main() {
+    println!("cargo:rerun-if-changed=Cargo.lock");
+
+    let lock = std::fs::read_to_string("Cargo.lock").unwrap_or_default();
+    let version = lock
+        .split("[[package]]")
+        .find(|package| package.contains("name = \"tree-sitter-lua\""))
+        .and_then(|package| {
+            package
+                .lines()
+                .find_map(|line| line.strip_prefix("version = "))
+                .map(|version| version.trim_matches('"').to_string())
+        })
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=TREE_SITTER_LUA_VERSION={version}");
+}
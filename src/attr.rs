@@ -2,11 +2,58 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
-use std::sync::LazyLock;
+use std::{
+    collections::{HashMap, HashSet},
+    ops::{Deref, DerefMut},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        LazyLock, OnceLock, RwLock,
+    },
+};
 
+#[cfg(feature = "pcre2")]
 use pcre2::bytes::{Regex, RegexBuilder};
 
-#[derive(Debug)]
+use crate::{
+    config::rename_types,
+    strings::{self, Text},
+};
+
+/// A parsed value along with the line it came from, for pointing warnings at it.
+#[derive(Debug, Clone)]
+pub struct Spanned<T> {
+    pub value: T,
+    /// The 1-based line in the source file, or `None` for things ldoc_gen added itself
+    pub line: Option<usize>,
+}
+
+impl<T> Spanned<T> {
+    pub fn new(value: T, line: Option<usize>) -> Self {
+        Spanned { value, line }
+    }
+}
+
+impl<T> From<T> for Spanned<T> {
+    fn from(value: T) -> Self {
+        Spanned::new(value, None)
+    }
+}
+
+impl<T> Deref for Spanned<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> DerefMut for Spanned<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+#[derive(Debug, Clone)]
 pub enum Attribute {
     Param {
         name: String,
@@ -20,33 +67,179 @@ pub enum Attribute {
     },
     Class {
         ty: String,
+        /// Type parameters like the `T` in `Container<T>`
+        generics: Vec<String>,
+        /// The classes after the `:` in `@class Foo : Bar, Baz`, without type arguments
+        parents: Vec<String>,
+    },
+    ClassMod {
+        /// Whether the class is the file's module. `@classmod` is only for those, so other
+        /// classes are documented as a `@type` in it instead.
+        is_module: bool,
+    },
+    Field {
+        name: String,
+        ty: String,
+        desc: Option<String>,
+        visibility: Visibility,
     },
-    ClassMod,
     See {
         link: String,
+        /// Only used as the text of URL links
         desc: Option<String>,
     },
     Alias {
-        types: String,
+        name: String,
+        members: Vec<AliasMember>,
     },
     NoDoc,
+    Nodiscard,
+    Deprecated {
+        message: Option<String>,
+    },
+    Package,
+    /// `---@private` on a function, rendered as `@local` like `@package`
+    Private,
+    Since {
+        version: String,
+    },
+    Version {
+        version: String,
+    },
+    Source {
+        source: String,
+    },
+    Within {
+        section: String,
+    },
+    /// `---@raise`, or `---@throws`, describing an error the function can throw
+    Raise {
+        desc: String,
+    },
+    /// An explicit `---@usage`, with the text after `@usage` and the lines up to the next
+    /// tag, minus the doc prefix
+    Usage {
+        lines: Vec<String>,
+    },
+    /// A `---@type fun(...)` on a variable, documenting it as a function
+    FunType {
+        /// Names and types
+        params: Vec<(String, String)>,
+        returns: Vec<String>,
+    },
+}
+
+/// One of the types in an `@alias`'s union.
+#[derive(Debug, Clone)]
+pub struct AliasMember {
+    pub ty: String,
+    /// The `# comment` after the type
+    pub desc: Option<String>,
+}
+
+/// LuaLS `@field` visibility modifiers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Visibility {
+    Public,
+    Protected,
+    Private,
+    Package,
+}
+
+impl Visibility {
+    pub fn from_keyword(keyword: &str) -> Option<Self> {
+        match keyword {
+            "public" => Some(Visibility::Public),
+            "protected" => Some(Visibility::Protected),
+            "private" => Some(Visibility::Private),
+            "package" => Some(Visibility::Package),
+            _ => None,
+        }
+    }
 }
 
 impl Attribute {
+    /// The LuaLS tag this attribute was parsed from.
+    pub fn tag(&self) -> &'static str {
+        match self {
+            Attribute::Param { .. } => "param",
+            Attribute::Return { .. } => "return",
+            Attribute::Class { .. } => "class",
+            Attribute::ClassMod { .. } => "classmod",
+            Attribute::Field { .. } => "field",
+            Attribute::See { .. } => "see",
+            Attribute::Alias { .. } => "alias",
+            Attribute::NoDoc => "nodoc",
+            Attribute::Nodiscard => "nodiscard",
+            Attribute::Deprecated { .. } => "deprecated",
+            Attribute::Package => "package",
+            Attribute::Private => "private",
+            Attribute::Since { .. } => "since",
+            Attribute::Raise { .. } => "raise",
+            Attribute::Version { .. } => "version",
+            Attribute::Source { .. } => "source",
+            Attribute::Within { .. } => "within",
+            Attribute::Usage { .. } => "usage",
+            Attribute::FunType { .. } => "type",
+        }
+    }
+
+    /// The parsed `fun(...)` type of a function-typed `@field`.
+    pub fn fun_type(&self) -> Option<Attribute> {
+        match self {
+            Attribute::Field { ty, .. } => parse_fun_type(ty),
+            _ => None,
+        }
+    }
+
     pub fn to_ldoc_string(&self) -> String {
         match self {
             Attribute::Param { name, ty, desc } => {
-                let ty = if ty.starts_with("fun(") {
-                    "function".to_string()
-                } else if ty.starts_with('{') {
-                    "table".to_string()
-                } else {
-                    let mut ty = ty.to_string().replace('?', "|nil");
-                    ty.retain(|c| !c.is_whitespace());
-                    ty
+                let (ty_str, desc) = format_literal_union(ty, desc.as_deref());
+                // LDoc's `[opt]` says it can be nil, so the type doesn't have to.
+                // Varargs can always be left out, so they keep their `nil`.
+                let (default, desc) = param_default(desc);
+                let (ty_str, optional) = match strip_nil(&ty_str) {
+                    Some(ty_str) if name != "..." => (ty_str, true),
+                    _ => (ty_str, false),
+                };
+                let opt = match default {
+                    Some(default) => format!("[opt={default}]"),
+                    None if optional => "[opt]".to_string(),
+                    None => String::new(),
                 };
+                let mut ret = format!(
+                    "---@tparam{opt} {ty_str} {name}{}",
+                    desc.as_ref()
+                        .map(|desc| {
+                            let mut ret = String::from(" ");
+                            ret.push_str(desc);
+                            ret
+                        })
+                        .unwrap_or("".to_string())
+                );
+                // LDoc documents the fields of table params as `name.field`
+                for (field, field_ty) in table_shape(ty).unwrap_or_default() {
+                    ret.push_str(&format!(
+                        "\n---@tparam {} {name}.{field}",
+                        format_type(&field_ty)
+                    ));
+                }
+                ret
+            }
+            Attribute::Return { ty, name, desc } => {
+                let shape = table_shape(ty);
+                let desc = return_description(name.as_deref(), desc.as_deref());
+                let (ty, desc) = format_literal_union(ty, desc.as_deref());
+                let desc = with_shape_note(desc, shape);
                 format!(
-                    "---@tparam {ty} {name}{}",
+                    "---@treturn {ty}{}{}",
+                    // LDoc has no variadic returns, so keep the `...` in front of the description
+                    if name.as_deref() == Some("...") {
+                        " ..."
+                    } else {
+                        ""
+                    },
                     desc.as_ref()
                         .map(|desc| {
                             let mut ret = String::from(" ");
@@ -56,11 +249,28 @@ impl Attribute {
                         .unwrap_or("".to_string())
                 )
             }
-            Attribute::Return { ty, name: _, desc } => {
-                let mut ty = ty.to_string().replace('?', "|nil");
-                ty.retain(|c| !c.is_whitespace());
+            Attribute::Class { ty, .. } => {
+                let ty = strip_whitespace(&ty.replace('?', "|nil"));
+                format!("---\n---@module {}", module_path(&ty))
+            }
+            Attribute::ClassMod { .. } => "---@classmod".to_string(), // TODO:
+            Attribute::Field {
+                name,
+                ty,
+                desc,
+                visibility,
+            } => {
+                let shape = table_shape(ty);
+                let (ty, desc) = format_literal_union(ty, desc.as_deref());
+                let desc = with_shape_note(desc, shape);
+                let note = match visibility {
+                    Visibility::Public => "",
+                    Visibility::Protected => " (protected)",
+                    Visibility::Private => " (private)",
+                    Visibility::Package => " (package)",
+                };
                 format!(
-                    "---@treturn {ty}{}",
+                    "---@tfield {ty} {name}{note}{}",
                     desc.as_ref()
                         .map(|desc| {
                             let mut ret = String::from(" ");
@@ -70,82 +280,1103 @@ impl Attribute {
                         .unwrap_or("".to_string())
                 )
             }
-            Attribute::Class { ty } => {
-                let mut ty = ty.to_string().replace('?', "|nil");
-                ty.retain(|c| !c.is_whitespace());
-                format!("---\n---@module {ty}")
+            // LDoc can't link to URLs, so they're written as a link in the description
+            Attribute::See { link, desc } if is_url(link) => {
+                format!(
+                    "---{}: [{}]({link})",
+                    strings::get(Text::SeeAlso),
+                    desc.as_deref().unwrap_or(link)
+                )
             }
-            Attribute::ClassMod => "---@classmod".to_string(), // TODO:
             Attribute::See { link, desc: _ } => {
                 format!("---@see {link}")
             }
-            Attribute::Alias { types: _ } => "".to_string(),
+            Attribute::Alias { .. } => "".to_string(),
             Attribute::NoDoc => "".to_string(),
+            Attribute::Nodiscard => "".to_string(),
+            Attribute::Deprecated { .. } => "".to_string(),
+            Attribute::Package | Attribute::Private => "---@local".to_string(),
+            Attribute::Since { version } | Attribute::Version { version } => {
+                format!("---@release {version}")
+            }
+            Attribute::Within { section } => format!("---@within {section}"),
+            Attribute::Raise { desc } => format!("---@raise {desc}"),
+            Attribute::Usage { lines } => {
+                format!("---@usage\n{}", usage_code(lines).trim_end())
+            }
+            Attribute::FunType { params, returns } => {
+                let params = params.iter().map(|(name, ty)| {
+                    Attribute::Param {
+                        name: name.clone(),
+                        ty: ty.clone(),
+                        desc: None,
+                    }
+                    .to_ldoc_string()
+                });
+                let returns = returns.iter().map(|ty| {
+                    Attribute::Return {
+                        ty: ty.clone(),
+                        name: None,
+                        desc: None,
+                    }
+                    .to_ldoc_string()
+                });
+                params.chain(returns).collect::<Vec<_>>().join("\n")
+            }
+            Attribute::Source { source } => {
+                format!("---{}: `{source}`", strings::get(Text::Source))
+            }
+        }
+    }
+}
+
+/// The description of a `@return`. LDoc has no names for returns, so a name is kept as the
+/// start of the description, which is also what `@return boolean the result` meant.
+/// The `...` of variadic returns is left out.
+pub fn return_description(name: Option<&str>, desc: Option<&str>) -> Option<String> {
+    match (name.filter(|name| *name != "..."), desc) {
+        (Some(name), Some(desc)) => Some(format!("{name} {desc}")),
+        (Some(name), None) => Some(name.to_string()),
+        (None, desc) => desc.map(str::to_string),
+    }
+}
+
+/// Convert a LuaLS type into something LDoc understands.
+fn format_type(ty: &str) -> String {
+    // A `|` after a function's returns is part of them, but `fun(x: number)|string` is a
+    // union like any other
+    if ty.starts_with("fun(") && (fun_has_returns(ty) || split_union(ty).len() == 1) {
+        "function".to_string()
+    } else if ty.starts_with('{') {
+        "table".to_string()
+    } else {
+        let ty = strip_whitespace(&ty.replace('?', "|nil"));
+        let members = split_union(&ty)
+            .into_iter()
+            .map(format_member)
+            .collect::<Vec<_>>();
+        rename_types(&members.join("|"))
+    }
+}
+
+/// Convert one member of a union. LDoc doesn't understand parentheses in types, so they're
+/// dropped from members like `(a|b)`. Arrays like `Foo[]` and `(a|b)[]` are written as
+/// LDoc's list type `{Foo,...}`. LDoc has no generics either, so `table<K, V>` is `table`
+/// and `Foo<T>` is `Foo`. Arrays of arrays and of generics like `table<K, V>[]` become
+/// `table`, since LDoc can't parse them inside a list type.
+fn format_member(member: &str) -> String {
+    // The `[]` in `fun(): string[]` belongs to the return type
+    if member.starts_with("fun(") {
+        return "function".to_string();
+    }
+    let base = member.trim_end_matches("[]");
+    let dims = (member.len() - base.len()) / 2;
+
+    // `(table<K, V>)[]` is the same as `table<K, V>[]`
+    let element = strip_parens(base).unwrap_or(base);
+    if dims > 0 && generic_base(element).is_some() {
+        return "table".to_string();
+    }
+    let base = if let Some(generic) = generic_base(base) {
+        generic.to_string()
+    } else if base.starts_with('{') {
+        "table".to_string()
+    } else if let Some(inner) = strip_parens(base) {
+        split_union(inner)
+            .into_iter()
+            .map(format_member)
+            .collect::<Vec<_>>()
+            .join("|")
+    } else {
+        base.to_string()
+    };
+
+    match dims {
+        0 => base,
+        // `((a|b)[])[]` is an array of arrays too
+        1 if !base.starts_with('{') && !base.contains('<') => format!("{{{base},...}}"),
+        _ => "table".to_string(),
+    }
+}
+
+/// The name of a generic type like the `table` in `table<K, V>`, if `ty` is one.
+fn generic_base(ty: &str) -> Option<&str> {
+    let (name, args) = ty.split_once('<')?;
+    (args.ends_with('>')
+        && !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || "_.".contains(c)))
+    .then_some(name)
+}
+
+/// Whether a type starting with `fun(` has returns, like `fun(x: number): string`.
+fn fun_has_returns(ty: &str) -> bool {
+    let mut depth = 0;
+    for (i, c) in ty.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return ty[i + 1..].trim_start().starts_with(':');
+                }
+            }
+            _ => (),
+        }
+    }
+    false
+}
+
+/// `ty` without the parentheses around it, if it's wrapped in a pair of them.
+fn strip_parens(ty: &str) -> Option<&str> {
+    let inner = ty.strip_prefix('(')?.strip_suffix(')')?;
+    let mut depth = 0;
+    for c in inner.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 0 => return None,
+            ')' => depth -= 1,
+            _ => (),
         }
     }
+    Some(inner)
+}
+
+/// Add a note with the full type of function types and arrays written as `table` in `ty`
+/// to `desc`, since LDoc only sees `function` and `table`.
+fn with_type_note(ty: &str, desc: Option<&str>) -> Option<String> {
+    let types = split_union(ty)
+        .into_iter()
+        .filter_map(|member| {
+            let fun = strip_parens(member.trim_end_matches('?')).unwrap_or(member);
+            // Arrays of functions are `{function,...}`, which loses the signature too
+            let array = member.trim_end_matches('?');
+            let element = array.trim_end_matches("[]");
+            let dims = &array[element.len()..];
+            let element = strip_parens(element).unwrap_or(element);
+            if let Some(Attribute::FunType { params, returns }) = parse_fun_type(fun) {
+                Some(format!("`{}`", fun_signature(&params, &returns)))
+            } else if let Some(Attribute::FunType { params, returns }) =
+                parse_fun_type(element).filter(|_| !dims.is_empty())
+            {
+                Some(format!("`({}){dims}`", fun_signature(&params, &returns)))
+            } else {
+                let member = strip_whitespace(member.trim_end_matches('?'));
+                let has_fun = member.contains("fun(");
+                let array =
+                    (format_member(&member) == "table" || has_fun) && member.ends_with("[]");
+                // Generics lose their type arguments
+                (array || generic_base(&member).is_some()).then(|| format!("`{member}`"))
+            }
+        })
+        .collect::<Vec<_>>();
+    if types.is_empty() {
+        return desc.map(str::to_string);
+    }
+
+    let note = format!("Type: {}.", types.join(", "));
+    Some(match desc {
+        Some(desc) => format!("{desc} ({note})"),
+        None => note,
+    })
+}
+
+/// [`format_type`], but literals like the ones in `"left"|"right"` or `1|2|3` become the
+/// type of their values, like `string` or `integer`, with the allowed values listed in the
+/// returned description. `true|false` is just `boolean`. Other members are kept, so
+/// `number|"auto"` is `number|string`.
+fn format_literal_union(ty: &str, desc: Option<&str>) -> (String, Option<String>) {
+    let desc = with_type_note(ty, desc);
+    let desc = desc.as_deref();
+    let ty = format_type(ty);
+    let members = split_union(&ty);
+
+    let literals = members
+        .iter()
+        .filter(|member| literal_type(member).is_some())
+        .collect::<Vec<_>>();
+    if literals.is_empty() {
+        return (ty, desc.map(str::to_string));
+    }
+
+    // The types are renamed already, so the literals' have to be too to match them
+    let mut types = Vec::<String>::new();
+    for member in members.iter().filter(|member| **member != "nil") {
+        let ty = match literal_type(member) {
+            Some(ty) => rename_types(ty),
+            None => member.to_string(),
+        };
+        if !types.contains(&ty) {
+            types.push(ty);
+        }
+    }
+    // Integers are numbers too, so `1|1.5` is just `number`
+    if types.contains(&rename_types("number")) {
+        let integer = rename_types("integer");
+        types.retain(|ty| *ty != integer);
+    }
+    let mut formatted = types.join("|");
+    if members.contains(&"nil") {
+        formatted.push_str("|nil");
+    }
+
+    let value = |literal: &str| {
+        if literal.starts_with(['"', '\'']) {
+            literal[1..literal.len() - 1].to_string()
+        } else {
+            literal.to_string()
+        }
+    };
+    // Alias expansion may have listed them already, and all of a boolean's values are
+    // allowed anyway
+    let listed = desc.is_some_and(|desc| {
+        literals
+            .iter()
+            .all(|literal| desc.contains(&format!("`{literal}`")))
+    });
+    let all_booleans = literals.contains(&&"true") && literals.contains(&&"false");
+    if listed || (all_booleans && types == [rename_types("boolean")]) {
+        return (formatted, desc.map(str::to_string));
+    }
+
+    let values = literals
+        .iter()
+        .map(|literal| format!("`{}`", value(literal)))
+        .collect::<Vec<_>>()
+        .join(", ");
+    // With other types in the union, the values are only some of what it can be
+    let mixed = members
+        .iter()
+        .any(|member| literal_type(member).is_none() && *member != "nil");
+    let note = if mixed {
+        format!("Known values: {values}.")
+    } else {
+        format!("One of: {values}.")
+    };
+    let desc = match desc {
+        Some(desc) => format!("{desc} ({note})"),
+        None => note,
+    };
+    (formatted, Some(desc))
+}
+
+/// The type of a literal type like `"left"`, `1`, `1.5`, or `true`, if `member` is one.
+fn literal_type(member: &str) -> Option<&'static str> {
+    let is_string = member.len() >= 2
+        && (member.starts_with('"') && member.ends_with('"')
+            || member.starts_with('\'') && member.ends_with('\''));
+    if is_string {
+        return Some("string");
+    }
+    if member == "true" || member == "false" {
+        return Some("boolean");
+    }
+    let number = member.strip_prefix('-').unwrap_or(member);
+    let is_hex = number
+        .strip_prefix("0x")
+        .or_else(|| number.strip_prefix("0X"))
+        .is_some_and(|hex| !hex.is_empty() && hex.chars().all(|c| c.is_ascii_hexdigit()));
+    if is_hex || number.parse::<u64>().is_ok() {
+        Some("integer")
+    } else if number.starts_with(|c: char| c.is_ascii_digit()) && number.parse::<f64>().is_ok() {
+        Some("number")
+    } else {
+        None
+    }
+}
+
+/// The fields of an inline table type like `{ timeout: number, retries?: integer }`.
+///
+/// Returns `None` for anything that isn't a simple list of named fields, like
+/// `{ [string]: number }`.
+fn table_shape(ty: &str) -> Option<Vec<(String, String)>> {
+    let inner = ty.trim().strip_prefix('{')?.strip_suffix('}')?;
+
+    split_top_level(inner, ',')
+        .into_iter()
+        .map(|field| {
+            let (name, ty) = field.split_once(':')?;
+            let (name, optional) = match name.trim().strip_suffix('?') {
+                Some(name) => (name, true),
+                None => (name.trim(), false),
+            };
+            if !is_name(name) {
+                return None;
+            }
+            let mut ty = ty.trim().to_string();
+            if optional {
+                ty.push_str("|nil");
+            }
+            Some((name.to_string(), ty))
+        })
+        .collect()
+}
+
+/// Add the fields of an inline table type to a description, since LDoc has nowhere else
+/// to put them for returns and fields.
+fn with_shape_note(desc: Option<String>, shape: Option<Vec<(String, String)>>) -> Option<String> {
+    let Some(shape) = shape.filter(|shape| !shape.is_empty()) else {
+        return desc;
+    };
+    let fields = shape
+        .iter()
+        .map(|(name, ty)| format!("`{name}`: {}", format_type(ty)))
+        .collect::<Vec<_>>()
+        .join(", ");
+    Some(match desc {
+        Some(desc) => format!("{desc} ({fields})"),
+        None => format!("({fields})"),
+    })
+}
+
+/// Remove whitespace from a type, except inside string literals.
+fn strip_whitespace(ty: &str) -> String {
+    let mut in_string = None;
+    ty.chars()
+        .filter(|&c| {
+            match (c, in_string) {
+                ('"' | '\'', None) => in_string = Some(c),
+                (c, Some(quote)) if c == quote => in_string = None,
+                _ => (),
+            }
+            in_string.is_some() || !c.is_whitespace()
+        })
+        .collect()
 }
 
 pub struct AttrRegexes {
+    #[cfg(feature = "pcre2")]
     pub param: Regex,
+    #[cfg(feature = "pcre2")]
     pub ret: Regex,
-    pub see: Regex,
-    pub class: Regex,
-    pub classmod: Regex,
+    /// `---@return ...` with no type
+    pub vararg_return: regex::Regex,
+    pub usage: regex::Regex,
+    pub see: regex::Regex,
+    pub class: regex::Regex,
+    pub classmod: regex::Regex,
+    #[cfg(feature = "pcre2")]
+    pub field: Regex,
+    #[cfg(feature = "pcre2")]
     pub alias: Regex,
-    pub example: Regex,
     pub fences: regex::Regex,
     pub nodoc: regex::Regex,
+    pub nodiscard: regex::Regex,
+    pub deprecated: regex::Regex,
+    pub package: regex::Regex,
+    /// `---@private`
+    pub private: regex::Regex,
+    pub since: regex::Regex,
+    /// `---@raise ...` and `---@throws ...`
+    pub raise: regex::Regex,
+    pub version: regex::Regex,
+    pub source: regex::Regex,
+    pub within: regex::Regex,
+    /// `---@type fun(...)`
+    pub fun_type: regex::Regex,
+    /// `---@cast`, `---@as`, and `---@type` lines, which only narrow types
+    pub cast: regex::Regex,
+    /// Inline `--[[@as Type]]` casts
+    pub inline_cast: regex::Regex,
+}
+
+static DOC_PREFIX: OnceLock<String> = OnceLock::new();
+
+/// Set what doc comments start with instead of `---`. This has to be called before
+/// any comments are parsed.
+pub fn set_doc_prefix(prefix: String) {
+    let _ = DOC_PREFIX.set(prefix);
+}
+
+/// What doc comments start with.
+pub fn doc_prefix() -> &'static str {
+    DOC_PREFIX.get().map(String::as_str).unwrap_or("---")
+}
+
+static TRACE_REGEX: AtomicBool = AtomicBool::new(false);
+
+/// Print why annotation lines didn't match for `--trace-regex`.
+pub fn enable_trace_regex() {
+    TRACE_REGEX.store(true, Ordering::Relaxed);
+}
+
+static ROOT_MODULE: OnceLock<String> = OnceLock::new();
+
+/// Set the `--root-module` prefix for module names. This has to be called before
+/// anything is rendered.
+pub fn set_root_module(prefix: String) {
+    let _ = ROOT_MODULE.set(prefix);
+}
+
+static MODULE_PREFIXES: LazyLock<RwLock<HashMap<String, String>>> = LazyLock::new(Default::default);
+
+/// Set the `[module_prefixes]` prefix of each module and class name, from the file it's
+/// declared in. This has to be called before anything is rendered, and again for each
+/// `--watch` run.
+pub fn set_module_prefixes(prefixes: HashMap<String, String>) {
+    *MODULE_PREFIXES.write().unwrap() = prefixes;
+}
+
+/// `name` as a module name in the output, with the prefix from `[module_prefixes]` or
+/// `--root-module` if there is one.
+pub fn module_path(name: &str) -> String {
+    let prefixes = MODULE_PREFIXES.read().unwrap();
+    let prefix = prefixes
+        .get(name)
+        .or(ROOT_MODULE.get())
+        .map(|prefix| prefix.trim_end_matches('.'));
+    match prefix {
+        Some(prefix) if !prefix.is_empty() => format!("{prefix}.{name}"),
+        _ => name.to_string(),
+    }
+}
+
+/// Replace the `---` in a pattern matching source comments with the doc prefix.
+pub fn with_prefix(pattern: &str) -> String {
+    pattern.replace("---", &regex::escape(doc_prefix()))
+}
+
+/// Parse a block of comment lines into two vectors: the first is the summary/body lines,
+/// and the second is the attribute lines converted into [`Attribute`]s. Each line comes
+/// with its line number, which the attribute parsed from it keeps.
+///
+/// Lines that aren't doc comments are ignored.
+pub fn attributes_from_comment_block(
+    lines: &[(&str, usize)],
+) -> (Vec<String>, Vec<Spanned<Attribute>>) {
+    // filter actual comments
+    let re = regex::Regex::new(&with_prefix(r"^[ \t]*---[ \t]*(@|\|)?")).unwrap();
+
+    // (line, whether it's a plain `--` note, line number)
+    let mut doc_lines = Vec::<(String, bool, usize)>::new();
+    for &(line, number) in lines {
+        let trimmed = line.trim();
+        if trimmed.len() >= 4 && trimmed.chars().all(|c| c == '-') {
+            // A `----` ruler is a paragraph break, unless it's above the block like a banner
+            if !doc_lines.is_empty() {
+                doc_lines.push((doc_prefix().to_string(), false, number));
+            }
+        } else if re.is_match(line) {
+            doc_lines.push((line.to_string(), false, number));
+        } else if IGNORE_DIRECTIVE.is_match(trimmed) {
+            // Works with any number of dashes, even outside a doc block
+            doc_lines.push((format!("{} ldoc_gen: ignore", doc_prefix()), false, number));
+        } else if let Some(note) = trimmed
+            .strip_prefix("--")
+            .filter(|note| !note.starts_with('['))
+        {
+            // Plain `--` comments between doc lines are part of the description, but never
+            // attributes. Commented out code and TODOs aren't documentation.
+            if !doc_lines.is_empty() && !is_code_comment(note) {
+                doc_lines.push((format!("{}{note}", doc_prefix()), true, number));
+            }
+        }
+    }
+    // Notes after the last doc line aren't inside the block
+    while doc_lines.last().is_some_and(|(_, is_note, _)| *is_note) {
+        doc_lines.pop();
+    }
+
+    let mut body = Vec::<String>::new();
+    let mut attributes = Vec::<Spanned<Attribute>>::new();
+    // Whether the previous line was a @param/@return whose description can continue
+    let mut in_attr_desc = false;
+    // Whether lines are part of a @usage until the next tag
+    let mut in_usage = false;
+    // Whether the previous line was a @param/@field/@return, or a `---|` value after one
+    let mut in_union = false;
+    for (text, is_note, number) in doc_lines.iter() {
+        let continues_union = std::mem::take(&mut in_union);
+        if in_usage {
+            let line = text.trim_start();
+            let line = line.strip_prefix(doc_prefix()).unwrap_or(line);
+            match attributes.last_mut().map(DerefMut::deref_mut) {
+                Some(Attribute::Usage { lines }) if !line.trim_start().starts_with('@') => {
+                    lines.push(line.to_string());
+                    continue;
+                }
+                _ => in_usage = false,
+            }
+        }
+        if *is_note {
+            in_attr_desc = false;
+            body.push(text.clone());
+            continue;
+        }
+        let text = text.as_str();
+        let attr = if let Some(param) = match_param(text) {
+            let name = param.name.unwrap_or_default();
+            // `x? T` is the same as `x T?`, or `x (T)?` so a function type's return doesn't
+            // get the `?`
+            let (name, ty) = match name.strip_suffix('?') {
+                Some(name) if param.ty.starts_with("fun(") => (name, format!("({})?", param.ty)),
+                Some(name) if !param.ty.ends_with('?') => (name, format!("{}?", param.ty)),
+                Some(name) => (name, param.ty.to_string()),
+                None => (name, param.ty.to_string()),
+            };
+            Some(Attribute::Param {
+                name: name.to_string(),
+                ty,
+                desc: param.desc.map(str::to_string),
+            })
+        } else if let Some(ret) = match_return(text).filter(|ret| !is_description_word(ret.ty)) {
+            Some(Attribute::Return {
+                ty: ret.ty.to_string(),
+                name: ret.name.map(str::to_string),
+                desc: ret.desc.map(str::to_string),
+            })
+        } else if let Some(captures) = ATTR_REGEXES.vararg_return.captures(text) {
+            // A bare `...` isn't a type, but LuaLS takes it as any number of anything
+            Some(Attribute::Return {
+                ty: "any".to_string(),
+                name: Some("...".to_string()),
+                desc: captures.name("desc").map(|desc| desc.as_str().to_string()),
+            })
+        } else if let Some(captures) = ATTR_REGEXES.usage.captures(text) {
+            in_usage = true;
+            Some(Attribute::Usage {
+                // Indented as if it were on its own `--- ` line
+                lines: vec![format!(" {}", &captures["first"])],
+            })
+        } else if let Some(captures) = ATTR_REGEXES.see.captures(text) {
+            Some(Attribute::See {
+                link: captures["link"].to_string(),
+                desc: captures.name("desc").map(|desc| desc.as_str().to_string()),
+            })
+        } else if let Some(captures) = ATTR_REGEXES.class.captures(text) {
+            Some(Attribute::Class {
+                ty: captures["ty"].to_string(),
+                // LDoc has no generics, so these only end up in the description
+                generics: captures
+                    .name("generics")
+                    .map(|generics| {
+                        split_top_level(generics.as_str(), ',')
+                            .into_iter()
+                            .map(str::to_string)
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+                parents: captures
+                    .name("parents")
+                    .map(|parents| {
+                        split_top_level(parents.as_str(), ',')
+                            .into_iter()
+                            .map(|parent| parent.split('<').next().unwrap_or(parent).to_string())
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+            })
+        } else if let Some(field) = match_field(text) {
+            Some(Attribute::Field {
+                name: field.name.unwrap_or_default().to_string(),
+                ty: field.ty.to_string(),
+                desc: field.desc.map(str::to_string),
+                visibility: field
+                    .vis
+                    .and_then(Visibility::from_keyword)
+                    .unwrap_or(Visibility::Public),
+            })
+        } else if ATTR_REGEXES.classmod.is_match(text) {
+            // Which class is the module is only known once the whole file is parsed
+            Some(Attribute::ClassMod { is_module: true })
+        } else if ATTR_REGEXES.nodoc.is_match(text) {
+            Some(Attribute::NoDoc)
+        } else if ATTR_REGEXES.nodiscard.is_match(text) {
+            Some(Attribute::Nodiscard)
+        } else if let Some(captures) = ATTR_REGEXES.deprecated.captures(text) {
+            Some(Attribute::Deprecated {
+                message: captures.name("message").map(|msg| msg.as_str().to_string()),
+            })
+        } else if ATTR_REGEXES.package.is_match(text) {
+            Some(Attribute::Package)
+        } else if ATTR_REGEXES.private.is_match(text) {
+            Some(Attribute::Private)
+        } else if let Some(captures) = ATTR_REGEXES.since.captures(text) {
+            Some(Attribute::Since {
+                version: captures["version"].to_string(),
+            })
+        } else if let Some(captures) = ATTR_REGEXES.raise.captures(text) {
+            Some(Attribute::Raise {
+                desc: captures["desc"].to_string(),
+            })
+        } else if let Some(captures) = ATTR_REGEXES.version.captures(text) {
+            Some(Attribute::Version {
+                version: captures["version"].to_string(),
+            })
+        } else if let Some(captures) = ATTR_REGEXES.within.captures(text) {
+            Some(Attribute::Within {
+                section: captures["section"].to_string(),
+            })
+        } else if let Some(captures) = ATTR_REGEXES.source.captures(text) {
+            Some(Attribute::Source {
+                source: captures["source"].to_string(),
+            })
+        } else if let Some(captures) = ATTR_REGEXES.fun_type.captures(text) {
+            in_attr_desc = false;
+            parse_fun_type(&captures["ty"])
+        } else if ATTR_REGEXES.cast.is_match(text) {
+            // Type narrowing isn't documentation, and LDoc chokes on it
+            in_attr_desc = false;
+            None
+        } else if !alias_ranges(text).is_empty() {
+            // These should have been extracted already, but don't let one leak into the body
+            None
+        } else {
+            // Fold `---` lines directly following a @param/@return into its description
+            let line = text.trim_start();
+            let line = line
+                .strip_prefix(doc_prefix())
+                .unwrap_or(line)
+                .trim_start_matches('-')
+                .trim();
+            // A `@return` without a type, like `@return # if it worked`, describes the one
+            // before it. Ones without a return before them are left for the caller to
+            // warn about.
+            let typeless = typeless_return(text);
+            let value = ALIAS_OTHER_LINE_REGEX
+                .captures(text)
+                .filter(|_| continues_union)
+                .and_then(|captures| {
+                    let ty = captures.name("type")?.as_str();
+                    // `---|>` marks the default and `---|+` an addition, neither matters here
+                    let ty = ty.trim_start_matches(['>', '+']);
+                    let ty = ty
+                        .chars()
+                        .filter(|c| !c.is_whitespace())
+                        .collect::<String>();
+                    let comment = captures
+                        .name("desc")
+                        .map(|desc| desc.as_str().trim())
+                        .filter(|desc| !desc.is_empty());
+                    (!ty.is_empty()).then_some((ty, comment))
+                });
+            match attributes.last_mut().map(DerefMut::deref_mut) {
+                // `---| "a" # comment` lines after one list the values it can be
+                Some(
+                    Attribute::Param { ty, desc, .. }
+                    | Attribute::Field { ty, desc, .. }
+                    | Attribute::Return { ty, desc, .. },
+                ) if value.is_some() => {
+                    let (value, comment) = value.unwrap_or_default();
+                    if let Some(comment) = comment {
+                        // Written like the list of values the literals get, see
+                        // `format_literal_union`
+                        let shown = match literal_type(&value) {
+                            Some("string") => &value[1..value.len() - 1],
+                            _ => value.as_str(),
+                        };
+                        let note = format!("`{shown}`: {comment}");
+                        *desc = Some(match desc.take() {
+                            Some(desc) => format!("{desc}; {note}"),
+                            None => note,
+                        });
+                    }
+                    ty.push('|');
+                    ty.push_str(&value);
+                    in_union = true;
+                    in_attr_desc = false;
+                }
+                Some(Attribute::Return { desc, .. })
+                    if typeless.is_some_and(|typeless| !typeless.is_empty()) =>
+                {
+                    let typeless = typeless.unwrap_or_default();
+                    match desc {
+                        Some(desc) => {
+                            desc.push(' ');
+                            desc.push_str(typeless);
+                        }
+                        None => *desc = Some(typeless.to_string()),
+                    }
+                    in_attr_desc = true;
+                }
+                Some(Attribute::Param { desc, .. } | Attribute::Return { desc, .. })
+                    if in_attr_desc && !line.is_empty() && !line.starts_with('@') =>
+                {
+                    match desc {
+                        Some(desc) => {
+                            desc.push(' ');
+                            desc.push_str(line);
+                        }
+                        None => *desc = Some(line.to_string()),
+                    }
+                }
+                _ => {
+                    if line.starts_with('@') && TRACE_REGEX.load(Ordering::Relaxed) {
+                        trace_unmatched(text, line);
+                    }
+                    in_attr_desc = false;
+                    body.push(text.to_string());
+                }
+            }
+            None
+        };
+
+        if let Some(attr) = attr {
+            in_attr_desc = matches!(attr, Attribute::Param { .. } | Attribute::Return { .. });
+            in_union = matches!(
+                attr,
+                Attribute::Param { .. } | Attribute::Field { .. } | Attribute::Return { .. }
+            );
+            attributes.push(Spanned::new(attr, Some(*number)));
+        }
+    }
+    (body, attributes)
+}
+
+/// The description of a `@return` line without a type, like `if it worked` for
+/// `---@return # if it worked`, or `None` if it isn't one. A bare `---@return` has an empty
+/// description.
+pub fn typeless_return(text: &str) -> Option<&str> {
+    let line = text.trim_start();
+    let line = line.strip_prefix(doc_prefix()).unwrap_or(line).trim_start();
+    let rest = line.strip_prefix("@return")?;
+    if !(rest.is_empty() || rest.starts_with([' ', '\t', '#', ':'])) {
+        return None;
+    }
+    if match_return(text).is_some_and(|ret| !is_description_word(ret.ty)) {
+        return None;
+    }
+    Some(rest.trim_start_matches([' ', '\t', '#', ':']).trim())
+}
+
+/// Whether a `@return`'s type is really the start of its description, like the `if` in
+/// `---@return if it worked`. Only keywords that can't be types are caught, since anything
+/// else could be a class name.
+fn is_description_word(ty: &str) -> bool {
+    !matches!(ty, "nil" | "true" | "false" | "function")
+        && ty.chars().all(|c| c.is_ascii_alphabetic())
+        && !crate::is_identifier(ty)
+}
+
+/// Whether an attribute's regex matches a line.
+type Matcher = fn(&str) -> bool;
+
+/// The matchers [`attributes_from_comment_block`] tries for lines with each tag, by name.
+fn tag_matchers(tag: &str) -> Vec<(&'static str, Matcher)> {
+    match tag {
+        "param" => vec![("param", |text| match_param(text).is_some())],
+        "return" => vec![
+            ("ret", |text| match_return(text).is_some()),
+            ("vararg_return", |text| {
+                ATTR_REGEXES.vararg_return.is_match(text)
+            }),
+        ],
+        "field" => vec![("field", |text| match_field(text).is_some())],
+        "alias" => vec![("alias", |text| !alias_ranges(text).is_empty())],
+        "usage" => vec![("usage", |text| ATTR_REGEXES.usage.is_match(text))],
+        "see" => vec![("see", |text| ATTR_REGEXES.see.is_match(text))],
+        "class" => vec![("class", |text| ATTR_REGEXES.class.is_match(text))],
+        "classmod" => vec![("classmod", |text| ATTR_REGEXES.classmod.is_match(text))],
+        "nodoc" => vec![("nodoc", |text| ATTR_REGEXES.nodoc.is_match(text))],
+        "nodiscard" => vec![("nodiscard", |text| ATTR_REGEXES.nodiscard.is_match(text))],
+        "deprecated" => vec![("deprecated", |text| ATTR_REGEXES.deprecated.is_match(text))],
+        "package" => vec![("package", |text| ATTR_REGEXES.package.is_match(text))],
+        "private" => vec![("private", |text| ATTR_REGEXES.private.is_match(text))],
+        "since" => vec![("since", |text| ATTR_REGEXES.since.is_match(text))],
+        "raise" | "throws" => vec![("raise", |text| ATTR_REGEXES.raise.is_match(text))],
+        "version" => vec![("version", |text| ATTR_REGEXES.version.is_match(text))],
+        "within" => vec![("within", |text| ATTR_REGEXES.within.is_match(text))],
+        "source" => vec![("source", |text| ATTR_REGEXES.source.is_match(text))],
+        "type" => vec![
+            ("fun_type", |text| ATTR_REGEXES.fun_type.is_match(text)),
+            ("cast", |text| ATTR_REGEXES.cast.is_match(text)),
+        ],
+        "cast" | "as" => vec![("cast", |text| ATTR_REGEXES.cast.is_match(text))],
+        _ => vec![],
+    }
+}
+
+/// Print which matchers were tried on an annotation line that none of them matched, and
+/// how far each one got, for `--trace-regex`. `tagged` is the line from its `@`.
+///
+/// Neither regex crate says where a failed match stopped, so how far a matcher got is
+/// the longest start of the line it matches on its own. For a `@param` with a broken type
+/// like `fun(a: number`, that's up to `fun`.
+fn trace_unmatched(text: &str, tagged: &str) {
+    let tag = tagged[1..]
+        .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .next()
+        .unwrap_or_default();
+    eprintln!("trace-regex: no attribute matched `{}`", text.trim());
+    let matchers = tag_matchers(tag);
+    if matchers.is_empty() {
+        eprintln!("  nothing handles @{tag}, so it stays in the description");
+    }
+    for (name, matches) in matchers {
+        let longest = text
+            .char_indices()
+            .map(|(i, _)| i)
+            .chain([text.len()])
+            .rev()
+            .find(|&end| matches(&text[..end]));
+        match longest {
+            Some(end) => eprintln!(
+                "  {name}: matched up to column {}, `{}`, then stopped at `{}`",
+                text[..end].chars().count(),
+                text[..end].trim(),
+                text[end..].trim()
+            ),
+            None => eprintln!("  {name}: didn't match any of it"),
+        }
+    }
+}
+
+/// A default value at the start of a `@param` description, like `default: 5` or
+/// `default = "foo"`.
+static PARAM_DEFAULT_REGEX: LazyLock<regex::Regex> = LazyLock::new(|| {
+    regex::Regex::new(
+        r#"^default[ \t]*[:=][ \t]*(?<value>"[^"]*"|'[^']*'|[^ \t,;]+)[,;]?([ \t]+(?<desc>.*))?$"#,
+    )
+    .unwrap()
+});
+
+/// The default value from a `@param` description and the rest of the description.
+fn param_default(desc: Option<String>) -> (Option<String>, Option<String>) {
+    let Some(captures) = desc
+        .as_deref()
+        .and_then(|desc| PARAM_DEFAULT_REGEX.captures(desc))
+    else {
+        return (None, desc);
+    };
+    (
+        Some(captures["value"].to_string()),
+        captures.name("desc").map(|desc| desc.as_str().to_string()),
+    )
+}
+
+/// `ty` without its `nil` member, if it's a union with one.
+fn strip_nil(ty: &str) -> Option<String> {
+    let members = split_union(ty);
+    let rest = members
+        .iter()
+        .filter(|member| **member != "nil")
+        .copied()
+        .collect::<Vec<_>>();
+    (rest.len() < members.len() && !rest.is_empty()).then(|| rest.join("|"))
+}
+
+/// Whether a `@see` link is a URL like `https://example.com/docs#setup` rather than a
+/// reference to something documented.
+pub fn is_url(link: &str) -> bool {
+    link.contains("://")
+}
+
+/// Whether the text of a plain `--` comment looks like commented out code or a TODO rather
+/// than a note, like `print("x")` or `TODO fix`.
+fn is_code_comment(note: &str) -> bool {
+    CODE_COMMENT_REGEX.is_match(note)
+}
+
+static CODE_COMMENT_REGEX: LazyLock<regex::Regex> = LazyLock::new(|| {
+    regex::Regex::new(concat!(
+        r"^[ \t]*(TODO|FIXME|XXX|HACK)\b",
+        r"|^[ \t]*(local|return|function|end)\b",
+        r"|^[ \t]*(if|elseif|for|while)\b.*\b(then|do)[ \t]*$",
+        r"|[\w\])]\(.*\)",
+        r#"|^[ \t]*[\w.:\[\]"']+[ \t]*=[^=]"#,
+        r"|;[ \t]*$",
+    ))
+    .unwrap()
+});
+
+/// `-- ldoc_gen: ignore`, which works like `@nodoc`.
+static IGNORE_DIRECTIVE: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"^--+[ \t]*ldoc_gen:[ \t]*ignore\b").unwrap());
+
+/// The parts of a `@param`, `@return`, or `@field` line.
+pub struct TypedLine<'a> {
+    pub name: Option<&'a str>,
+    pub ty: &'a str,
+    pub desc: Option<&'a str>,
+    /// The visibility keyword of a `@field`
+    pub vis: Option<&'a str>,
+}
+
+#[cfg(feature = "pcre2")]
+fn match_typed<'a>(regex: &Regex, text: &'a str) -> Option<TypedLine<'a>> {
+    let captures = regex.captures(text.as_bytes()).ok()??;
+    let get = |name| {
+        captures
+            .name(name)
+            .and_then(|m| std::str::from_utf8(m.as_bytes()).ok())
+    };
+    Some(TypedLine {
+        // `@return number... counts` is variadic too
+        name: get("name").or(get("dots")),
+        ty: get("ty")?,
+        // `@return boolean # whether it worked` has a comment but no name
+        desc: get("desc").or(get("comment")).or(get("vdesc")),
+        vis: get("vis"),
+    })
+}
+
+#[cfg(feature = "pcre2")]
+fn match_param(text: &str) -> Option<TypedLine<'_>> {
+    match_typed(&ATTR_REGEXES.param, text)
+}
+
+#[cfg(feature = "pcre2")]
+fn match_return(text: &str) -> Option<TypedLine<'_>> {
+    match_typed(&ATTR_REGEXES.ret, text)
+}
+
+#[cfg(feature = "pcre2")]
+fn match_field(text: &str) -> Option<TypedLine<'_>> {
+    match_typed(&ATTR_REGEXES.field, text)
+}
+
+/// Find every `@alias` in `source`, along with its `---|` lines.
+#[cfg(feature = "pcre2")]
+fn alias_ranges(source: &str) -> Vec<std::ops::Range<usize>> {
+    ATTR_REGEXES
+        .alias
+        .find_iter(source.as_bytes())
+        .filter_map(|m| m.ok())
+        .map(|m| m.start()..m.end())
+        .collect()
+}
+
+#[cfg(not(feature = "pcre2"))]
+use crate::type_parser::{
+    aliases as alias_ranges, field as match_field, param as match_param, ret as match_return,
+};
+
+/// Build a PCRE2 regex where `\w` and friends match non-ASCII letters too.
+#[cfg(feature = "pcre2")]
+fn unicode_regex(pattern: &str) -> Result<Regex, pcre2::Error> {
+    RegexBuilder::new().utf(true).ucp(true).build(pattern)
 }
 
 pub static ATTR_REGEXES: LazyLock<AttrRegexes> = LazyLock::new(|| {
     AttrRegexes {
         // This is not fun
-        param: Regex::new(
-            r#"^[ \t]*---[ \t]*@param[ \t]+(?<name>\w+|\.\.\.)[ \t]+(?<ty>(((\{.*\}|table\<(?2),[ \t]*(?2)\>|fun\((\w+:[ \t]*(?2))?(,[ \t]*(?6))*[ \t]*\)(:[ \t]*(?2))?|\w+|".*")(\[\])?\??)|\((?2)\)(\[\])?\??)([ \t]*\|[ \t]*(?2))*)([ \t]+(?<desc>.*$))?"#
-        ).unwrap(),
-        ret: Regex::new(
-            r#"^[ \t]*---[ \t]*@return[ \t]+(?<ty>(((\{.*\}|table\<(?1),[ \t]*(?1)\>|fun\((\w+:[ \t]*(?1))?(,[ \t]*(?5))*[ \t]*\)(:[ \t]*(?1))?|\w+|".*")(\[\])?\??)|\((?1)\)(\[\])?\??)([ \t]*\|[ \t]*(?1))*)([ \t]+(?<name>\w+)([ \t]+(?<desc>.*$))?)?"#
-        ).unwrap(),
-        see: Regex::new(r"^[ \t]*---[ \t]*@see[ \t]+(?<link>\w+(\.\w+)?)([ \t]+(?<desc>.*$))?")
+        #[cfg(feature = "pcre2")]
+        param: unicode_regex(&with_prefix(
+            r#"^[ \t]*---[ \t]*@param[ \t]+(?<name>(?:\w+(?:\.\w+)*|\.\.\.)\??)[ \t]+(?<ty>(((\{[^{}]*(?:\{[^{}]*\}[^{}]*)*\}|table\<(?2),[ \t]*(?2)\>|fun\((\w+\??:[ \t]*(?2))?(,[ \t]*(?6))*[ \t]*\)(:[ \t]*(?:\((?:\w+\??:[ \t]*)?(?2)(?:,[ \t]*(?:\w+\??:[ \t]*)?(?2))*\)|(?:\w+\??:[ \t]*)?(?2)(?:,[ \t]*(?:\w+\??:[ \t]*)?(?2))*))?|-?\d[\w.]*|\w+|"[^"]*"|'[^']*')(\[\])*\??)|\((?2)\)(\[\])*\??)([ \t]*\|[ \t]*(?2))*)([ \t]+(?<desc>.*$))?"#
+        )).unwrap(),
+        #[cfg(feature = "pcre2")]
+        ret: unicode_regex(&with_prefix(
+            r#"^[ \t]*---[ \t]*@return[ \t]+(?<ty>(((\{[^{}]*(?:\{[^{}]*\}[^{}]*)*\}|table\<(?1),[ \t]*(?1)\>|fun\((\w+\??:[ \t]*(?1))?(,[ \t]*(?5))*[ \t]*\)(:[ \t]*(?:\((?:\w+\??:[ \t]*)?(?1)(?:,[ \t]*(?:\w+\??:[ \t]*)?(?1))*\)|(?:\w+\??:[ \t]*)?(?1)(?:,[ \t]*(?:\w+\??:[ \t]*)?(?1))*))?|-?\d[\w.]*|\w+|"[^"]*"|'[^']*')(\[\])*\??)|\((?1)\)(\[\])*\??)([ \t]*\|[ \t]*(?1))*)((?<dots>\.\.\.)([ \t]+(#[ \t]*)?(?<vdesc>.*$))?|[ \t]*#[ \t]*(?<comment>.*$)|[ \t]+(?<name>\w+|\.\.\.)([ \t]+(#[ \t]*)?(?<desc>.*$))?)?"#
+        )).unwrap(),
+        vararg_return: regex::Regex::new(&with_prefix(r"^[ \t]*---[ \t]*@return[ \t]+\.\.\.([ \t]+(#[ \t]*)?(?<desc>.*))?$")).unwrap(),
+        usage: regex::Regex::new(&with_prefix(r"^[ \t]*---[ \t]*@usage\b[ \t]*(?<first>.*)$")).unwrap(),
+        see: regex::Regex::new(&with_prefix(r"^[ \t]*---[ \t]*@see[ \t]+(?<link>[a-zA-Z][a-zA-Z0-9+.-]*://[^ \t]+|\w+([.:]\w+)*(#[\w.:-]+)?)([ \t]+(?<desc>.*$))?"))
             .unwrap(),
-        class: Regex::new(r"^[ \t]*---[ \t]*@class[ \t]+(?<ty>\w+)").unwrap(),
-        classmod: Regex::new(r"^[ \t]*---[ \t]*@classmod").unwrap(),
-        alias: Regex::new(
-            r#"^[ \t]*---[ \t]*@alias[ \t]+(?<name>\w+)[ \t]+(?<ty>(((\{.*\}|table\<(?2),[ \t]*(?2)\>|fun\((\w+:[ \t]*(?2))?(,[ \t]*(?6))*[ \t]*\)(:[ \t]*(?2))?|\w+|".*")(\[\])?\??)|\((?2)\)(\[\])?\??)([ \t]*\|[ \t]*(?2))*)(\s+---[ \t]*\|[ \t]*(?2)([ \t]+(#|--)?[ \t]*.*$)?)*"#
-        ).unwrap(),
-        example: RegexBuilder::new().multi_line(true).build(r"(^[ \t]*---[ \t]*#{1,5}[ \t]*[E|e]xamples?.*$\s*([ \t]*---\s*)*---[ \t]*```.*$(?<example>(.*$\s*)*?)[ \t]*---[ \t]*```\s*)").unwrap(),
+        class: regex::Regex::new(&with_prefix(r"^[ \t]*---[ \t]*@class[ \t]+(?<ty>\w+)(<(?<generics>[^>]*)>)?([ \t]*:[ \t]*(?<parents>[\w.]+(<[^>]*>)?([ \t]*,[ \t]*[\w.]+(<[^>]*>)?)*))?")).unwrap(),
+        classmod: regex::Regex::new(&with_prefix(r"^[ \t]*---[ \t]*@classmod")).unwrap(),
+        #[cfg(feature = "pcre2")]
+        field: unicode_regex(&with_prefix(
+            r#"^[ \t]*---[ \t]*@field[ \t]+((?<vis>public|protected|private|package)[ \t]+)?(?<name>\w+)[ \t]+(?<ty>(((\{[^{}]*(?:\{[^{}]*\}[^{}]*)*\}|table\<(?4),[ \t]*(?4)\>|fun\((\w+\??:[ \t]*(?4))?(,[ \t]*(?8))*[ \t]*\)(:[ \t]*(?:\((?:\w+\??:[ \t]*)?(?4)(?:,[ \t]*(?:\w+\??:[ \t]*)?(?4))*\)|(?:\w+\??:[ \t]*)?(?4)(?:,[ \t]*(?:\w+\??:[ \t]*)?(?4))*))?|-?\d[\w.]*|\w+|"[^"]*"|'[^']*')(\[\])*\??)|\((?4)\)(\[\])*\??)([ \t]*\|[ \t]*(?4))*)([ \t]+(?<desc>.*$))?"#
+        )).unwrap(),
+        #[cfg(feature = "pcre2")]
+        alias: RegexBuilder::new().utf(true).ucp(true).multi_line(true).build(&with_prefix(
+            r#"^[ \t]*---[ \t]*@alias[ \t]+(?<name>\w+)([ \t]+(?<ty>(((\{[^{}]*(?:\{[^{}]*\}[^{}]*)*\}|table\<(?3),[ \t]*(?3)\>|fun\((\w+\??:[ \t]*(?3))?(,[ \t]*(?7))*[ \t]*\)(:[ \t]*(?:\((?:\w+\??:[ \t]*)?(?3)(?:,[ \t]*(?:\w+\??:[ \t]*)?(?3))*\)|(?:\w+\??:[ \t]*)?(?3)(?:,[ \t]*(?:\w+\??:[ \t]*)?(?3))*))?|-?\d[\w.]*|\w+|"[^"]*"|'[^']*')(\[\])*\??)|\((?3)\)(\[\])*\??)([ \t]*\|[ \t]*(?3))*))?([ \t]+(#|--)[ \t]*.*$)?[ \t]*$(\s+---[ \t]*\|[>+]?[ \t]*(?3)([ \t]+(#|--)?[ \t]*.*$)?)*"#
+        )).unwrap(),
         fences: regex::RegexBuilder::new(r"(^[ \t]*---[ \t]*```.*\s*(?<lines>([ \t]*---[ \t]*(?<line>(.*$\s*)))*?)[ \t]*---[ \t]*```)").multi_line(true).build().unwrap(),
-        nodoc: regex::Regex::new(r"^[ \t]*---[ \t]*@nodoc").unwrap(),
+        nodoc: regex::Regex::new(&with_prefix(r"^[ \t]*---[ \t]*(@nodoc|ldoc_gen:[ \t]*ignore\b)")).unwrap(),
+        nodiscard: regex::Regex::new(&with_prefix(r"^[ \t]*---[ \t]*@nodiscard")).unwrap(),
+        deprecated: regex::Regex::new(&with_prefix(r"^[ \t]*---[ \t]*@deprecated([ \t]+(?<message>.*\S))?")).unwrap(),
+        package: regex::Regex::new(&with_prefix(r"^[ \t]*---[ \t]*@package")).unwrap(),
+        private: regex::Regex::new(&with_prefix(r"^[ \t]*---[ \t]*@private\b")).unwrap(),
+        since: regex::Regex::new(&with_prefix(r"^[ \t]*---[ \t]*@since[ \t]+(?<version>.*\S)")).unwrap(),
+        raise: regex::Regex::new(&with_prefix(r"^[ \t]*---[ \t]*@(raise|throws)[ \t]+(?<desc>.*\S)")).unwrap(),
+        version: regex::Regex::new(&with_prefix(r"^[ \t]*---[ \t]*@version[ \t]+(?<version>.*\S)")).unwrap(),
+        source: regex::Regex::new(&with_prefix(r"^[ \t]*---[ \t]*@source[ \t]+(?<source>.*\S)")).unwrap(),
+        within: regex::Regex::new(&with_prefix(r"^[ \t]*---[ \t]*@within[ \t]+(?<section>.*\S)")).unwrap(),
+        fun_type: regex::Regex::new(&with_prefix(r"^[ \t]*---[ \t]*@type[ \t]+(?<ty>fun\(.*\S)")).unwrap(),
+        cast: regex::Regex::new(&with_prefix(r"^[ \t]*---[ \t]*@(cast|as|type)\b")).unwrap(),
+        inline_cast: regex::Regex::new(r"[ \t]*--\[=*\[[ \t]*@as\b[^\]]*\]=*\]").unwrap(),
     }
 });
 
+/// Build the regex matching example code blocks under a markdown heading
+/// starting with one of `headings`, like `--- ### Example`.
+///
+/// Headings are matched case-insensitively.
+pub fn example_regex(headings: &[String]) -> regex::Regex {
+    let headings = headings
+        .iter()
+        .map(|heading| regex::escape(heading))
+        .collect::<Vec<_>>()
+        .join("|");
+    regex::RegexBuilder::new(&format!(r"(^[ \t]*---[ \t]*#{{1,5}}[ \t]*(?i:{headings})\b.*$\s*([ \t]*---\s*)*---[ \t]*```.*$(?<example>(.*$\s*)*?)[ \t]*---[ \t]*```\s*)"))
+        .multi_line(true)
+        .build()
+        .unwrap()
+}
+
 /// Replace all --- ### Examples with ---@usage
-pub fn replace_examples(source: &mut String) {
-    let captures = ATTR_REGEXES
-        .example
-        .captures_iter(source.as_bytes())
-        .filter_map(|res| res.ok())
-        .collect::<Vec<_>>();
+pub fn replace_examples(source: &mut String, example_regex: &regex::Regex) {
     let mut new_string = source.clone();
-    for capture in captures {
+    for capture in example_regex.captures_iter(source) {
         if let Some(example) = capture.name("example") {
-            if let Ok(example) = std::str::from_utf8(example.as_bytes()) {
-                let mut s = String::new();
-                s.push_str("---@usage");
-                s.push_str(example);
-                new_string = new_string.replace(
-                    std::str::from_utf8(capture.get(1).unwrap().as_bytes()).unwrap(),
-                    &s,
-                );
-            }
-        } else {
-            eprintln!("NO CAPTURES");
+            let mut s = String::new();
+            s.push_str("---@usage\n");
+            s.push_str(&normalize_example(example.as_str()));
+            new_string = new_string.replace(capture.get(1).unwrap().as_str(), &s);
         }
     }
 
     *source = new_string;
 }
 
+/// The code of every example in `text` that [`replace_examples`] would turn into a
+/// `@usage`, formatted like [`usage_code`].
+pub fn example_codes(text: &str, example_regex: &regex::Regex) -> Vec<String> {
+    example_regex
+        .captures_iter(text)
+        .filter_map(|capture| capture.name("example"))
+        .map(|example| normalize_example(example.as_str()))
+        .collect()
+}
+
+/// The code of an explicit `@usage`, as `--- ` lines with their common indentation removed.
+pub fn usage_code(lines: &[String]) -> String {
+    let example = lines
+        .iter()
+        .map(|line| format!("---{line}\n"))
+        .collect::<String>();
+    normalize_example(&example)
+}
+
+/// Strip the `---` prefixes and common indentation from example lines, then re-prefix
+/// them uniformly with `--- `. Tabs are expanded to four spaces.
+fn normalize_example(example: &str) -> String {
+    let mut lines = example
+        .lines()
+        .map(|line| {
+            line.trim_start()
+                .strip_prefix("---")
+                .unwrap_or(line)
+                .replace('\t', "    ")
+        })
+        .skip_while(|line| line.trim().is_empty())
+        .collect::<Vec<_>>();
+    while lines.last().is_some_and(|line| line.trim().is_empty()) {
+        lines.pop();
+    }
+
+    let indent = lines
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+
+    let mut ret = String::new();
+    for line in lines {
+        if line.trim().is_empty() {
+            ret.push_str("---\n");
+        } else {
+            ret.push_str("--- ");
+            ret.push_str(&line[indent..]);
+            ret.push('\n');
+        }
+    }
+    ret
+}
+
 /// Replace all non example code fences with indents.
 pub fn replace_fences(source: &mut String) {
     let mut new_lines = source.clone();
@@ -173,37 +1404,104 @@ pub fn replace_fences(source: &mut String) {
 /// Extract all @alias from the source, removing them and returning them as [`Attribute`]s.
 pub fn extract_alias(source: &mut String) -> Vec<Attribute> {
     let new_source = source.clone();
-    let mut matches = ATTR_REGEXES
-        .alias
-        .find_iter(new_source.as_bytes())
-        .collect::<Vec<_>>();
-    matches.reverse();
+    let mut ranges = alias_ranges(&new_source);
+    ranges.reverse();
 
     let mut ret = vec![];
-    for m in matches {
-        let Ok(m) = m else {
-            continue;
-        };
-        ret.push(std::str::from_utf8(m.as_bytes()).unwrap());
-        source.replace_range(m.start()..m.end(), "");
+    for range in ranges {
+        let alias = &new_source[range.clone()];
+        ret.push(alias);
+        // Keep the newlines so line numbers don't change
+        let newlines = "\n".repeat(alias.matches('\n').count());
+        source.replace_range(range, &newlines);
     }
 
     ret.into_iter().filter_map(parse_alias).collect()
 }
 
+/// LuaLS tags recognized after two dashes for `--two-dash`.
+const TWO_DASH_TAGS: &[&str] = &[
+    "alias",
+    "as",
+    "async",
+    "cast",
+    "class",
+    "classmod",
+    "deprecated",
+    "diagnostic",
+    "enum",
+    "field",
+    "generic",
+    "meta",
+    "module",
+    "nodiscard",
+    "nodoc",
+    "operator",
+    "overload",
+    "package",
+    "param",
+    "private",
+    "protected",
+    "return",
+    "see",
+    "since",
+    "source",
+    "type",
+    "version",
+    "within",
+];
+
+static TWO_DASH_REGEX: LazyLock<regex::Regex> = LazyLock::new(|| {
+    regex::RegexBuilder::new(&format!(
+        r"^([ \t]*)--([ \t]*(@({})\b|\|))",
+        TWO_DASH_TAGS.join("|")
+    ))
+    .multi_line(true)
+    .build()
+    .unwrap()
+});
+
+/// Turn annotations written with two dashes, like `--@param`, into doc comments so
+/// they're parsed like any other. Plain `-- comments` are left alone.
+pub fn promote_two_dash(source: &mut String) {
+    let prefix = doc_prefix().replace('$', "$$");
+    *source = TWO_DASH_REGEX
+        .replace_all(source, format!("${{1}}{prefix}${{2}}"))
+        .into_owned();
+}
+
+/// Find any `@alias` lines [`extract_alias`] didn't manage to extract, returning
+/// their 0-based line numbers.
+pub fn leftover_aliases(source: &str) -> Vec<usize> {
+    source
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| LEFTOVER_ALIAS_REGEX.is_match(line))
+        .map(|(i, _)| i)
+        .collect()
+}
+
+static LEFTOVER_ALIAS_REGEX: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(&with_prefix(r"^[ \t]*---[ \t]*@alias\b")).unwrap());
+
 fn parse_alias(alias: &str) -> Option<Attribute> {
-    let mut types = String::new();
     let mut lines = alias.lines();
 
-    types.push_str(
-        &ALIAS_FIRST_LINE_REGEX
-            .captures(lines.next()?)?
-            .name("types")?
-            .as_str()
-            .chars()
-            .filter(|c| !c.is_whitespace())
-            .collect::<String>(),
-    );
+    let first_line = ALIAS_FIRST_LINE_REGEX.captures(lines.next()?)?;
+    let name = first_line.name("name")?.as_str().to_string();
+
+    let mut members = Vec::new();
+    if let Some(types) = first_line.name("types") {
+        let desc = first_line
+            .name("desc")
+            .map(|desc| desc.as_str().trim().to_string());
+        for ty in split_union(types.as_str()) {
+            members.push(AliasMember {
+                ty: ty.chars().filter(|c| !c.is_whitespace()).collect(),
+                desc: desc.clone(),
+            });
+        }
+    }
 
     for line in lines {
         let Some(captures) = ALIAS_OTHER_LINE_REGEX.captures(line) else {
@@ -212,22 +1510,618 @@ fn parse_alias(alias: &str) -> Option<Attribute> {
         let Some(ty) = captures.name("type") else {
             continue;
         };
-        types.push('|');
-        types.push_str(
-            &ty.as_str()
-                .chars()
-                .filter(|c| !c.is_whitespace())
-                .collect::<String>(),
-        );
+        members.push(AliasMember {
+            ty: ty.as_str().chars().filter(|c| !c.is_whitespace()).collect(),
+            desc: captures
+                .name("desc")
+                .map(|desc| desc.as_str().trim().to_string())
+                .filter(|desc| !desc.is_empty()),
+        });
+    }
+
+    Some(Attribute::Alias { name, members })
+}
+
+/// Split a union type into its members, ignoring `|`s nested in parentheses,
+/// braces, angle brackets, or strings.
+pub fn split_union(ty: &str) -> Vec<&str> {
+    split_top_level(ty, '|')
+}
+
+/// Split `ty` on `sep`, ignoring any nested in parentheses, braces, angle brackets,
+/// or strings.
+fn split_top_level(ty: &str, sep: char) -> Vec<&str> {
+    let mut members = Vec::new();
+    let mut depth = 0i32;
+    let mut in_string = false;
+    let mut start = 0;
+
+    for (i, c) in ty.char_indices() {
+        match c {
+            '"' | '\'' => in_string = !in_string,
+            '(' | '{' | '<' | '[' if !in_string => depth += 1,
+            ')' | '}' | '>' | ']' if !in_string => depth -= 1,
+            c if c == sep && !in_string && depth == 0 => {
+                members.push(ty[start..i].trim());
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    members.push(ty[start..].trim());
+
+    members.retain(|member| !member.is_empty());
+    members
+}
+
+/// A readable `fun(a: number): string` from the parts of a function type.
+fn fun_signature(params: &[(String, String)], returns: &[String]) -> String {
+    let params = params
+        .iter()
+        .map(|(name, ty)| format!("{name}: {ty}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    if returns.is_empty() {
+        format!("fun({params})")
+    } else {
+        format!("fun({params}): {}", returns.join(", "))
     }
+}
+
+/// Parse a `fun(a: number, b?: string): string` type into an [`Attribute::FunType`].
+pub fn parse_fun_type(ty: &str) -> Option<Attribute> {
+    let ty = ty.trim().strip_prefix("fun(")?;
+
+    // Find the `)` closing the parameter list
+    let mut depth = 1;
+    let end = ty.char_indices().find_map(|(i, c)| {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            _ => (),
+        }
+        (depth == 0).then_some(i)
+    })?;
+
+    let params = split_top_level(&ty[..end], ',')
+        .into_iter()
+        .map(|param| {
+            let (name, ty) = param.split_once(':').unwrap_or((param, "any"));
+            let mut ty = ty.trim().to_string();
+            let name = match name.trim().strip_suffix('?') {
+                Some(name) => {
+                    ty.push_str("|nil");
+                    name
+                }
+                None => name.trim(),
+            };
+            (name.to_string(), ty)
+        })
+        .collect();
+
+    let returns = ty[end + 1..]
+        .trim()
+        .strip_prefix(':')
+        .map(|returns| {
+            let returns = returns.trim();
+            let returns = returns
+                .strip_prefix('(')
+                .and_then(|returns| returns.strip_suffix(')'))
+                .unwrap_or(returns);
+            split_top_level(returns, ',')
+                .into_iter()
+                .map(|ret| {
+                    // Named returns like `(ok: boolean)`
+                    match ret.split_once(':') {
+                        Some((name, ty)) if is_name(name.trim()) => ty.trim().to_string(),
+                        _ => ret.to_string(),
+                    }
+                })
+                .collect()
+        })
+        .unwrap_or_default();
 
-    Some(Attribute::Alias { types })
+    Some(Attribute::FunType { params, returns })
+}
+
+fn is_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// Replace alias names in the types of `@param`s, `@return`s, and `@field`s with the
+/// union of the alias's types, adding any descriptions of the alias's members
+/// to the attribute's description.
+pub fn expand_aliases(
+    attrs: &mut [Spanned<Attribute>],
+    aliases: &HashMap<String, Vec<AliasMember>>,
+) {
+    for attr in attrs.iter_mut().map(DerefMut::deref_mut) {
+        let (Attribute::Param { ty, desc, .. }
+        | Attribute::Return { ty, desc, .. }
+        | Attribute::Field { ty, desc, .. }) = attr
+        else {
+            continue;
+        };
+
+        let mut expanded = Vec::<String>::new();
+        let mut notes = Vec::<String>::new();
+        for member in split_union(ty) {
+            let (name, optional) = match member.strip_suffix('?') {
+                Some(name) => (name, true),
+                None => (member, false),
+            };
+            let Some(alias) = aliases.get(name) else {
+                expanded.push(member.to_string());
+                continue;
+            };
+            for alias_member in alias {
+                expanded.push(alias_member.ty.clone());
+            }
+            if optional {
+                expanded.push("nil".to_string());
+            }
+            if alias.iter().any(|alias_member| alias_member.desc.is_some()) {
+                notes.extend(alias.iter().map(|alias_member| match &alias_member.desc {
+                    Some(member_desc) => format!("`{}`: {member_desc}", alias_member.ty),
+                    None => format!("`{}`", alias_member.ty),
+                }));
+            }
+        }
+
+        *ty = expanded.join("|");
+        if !notes.is_empty() {
+            let notes = format!("({})", notes.join(", "));
+            match desc {
+                Some(desc) => {
+                    desc.push(' ');
+                    desc.push_str(&notes);
+                }
+                None => *desc = Some(notes),
+            }
+        }
+    }
+}
+
+/// Expand aliases whose members are other aliases, so `@alias A B|C` with `@alias B X|Y`
+/// becomes `X|Y|C`.
+///
+/// Returns the cycles found, like `["A", "B", "A"]`. References that would loop are left
+/// as they are.
+pub fn resolve_aliases(aliases: &mut HashMap<String, Vec<AliasMember>>) -> Vec<Vec<String>> {
+    fn resolve(
+        name: &str,
+        aliases: &HashMap<String, Vec<AliasMember>>,
+        stack: &mut Vec<String>,
+        cycles: &mut Vec<Vec<String>>,
+    ) -> Vec<AliasMember> {
+        stack.push(name.to_string());
+        let mut resolved = Vec::<AliasMember>::new();
+        for member in aliases.get(name).into_iter().flatten() {
+            let (ty, optional) = match member.ty.strip_suffix('?') {
+                Some(ty) => (ty, true),
+                None => (member.ty.as_str(), false),
+            };
+            if !aliases.contains_key(ty) {
+                resolved.push(member.clone());
+                continue;
+            }
+            if let Some(start) = stack.iter().position(|seen| seen == ty) {
+                let mut cycle = stack[start..].to_vec();
+                cycle.push(ty.to_string());
+                cycles.push(cycle);
+                resolved.push(member.clone());
+                continue;
+            }
+
+            resolved.extend(resolve(ty, aliases, stack, cycles));
+            if optional {
+                resolved.push(AliasMember {
+                    ty: "nil".to_string(),
+                    desc: None,
+                });
+            }
+        }
+        stack.pop();
+
+        // `A|B` where both include `nil` should only list it once
+        let mut seen = HashSet::<String>::new();
+        resolved.retain(|member| seen.insert(member.ty.clone()));
+        resolved
+    }
+
+    let mut names = aliases.keys().cloned().collect::<Vec<_>>();
+    // Sorted so cycles are reported the same way every run
+    names.sort();
+
+    let mut cycles = Vec::new();
+    let resolved = names
+        .into_iter()
+        .map(|name| {
+            let members = resolve(&name, aliases, &mut Vec::new(), &mut cycles);
+            (name, members)
+        })
+        .collect::<Vec<_>>();
+    aliases.extend(resolved);
+
+    // Each cycle is found once from every alias in it, so keep one per set of aliases
+    let mut seen = HashSet::<Vec<String>>::new();
+    cycles.retain(|cycle| {
+        let mut key = cycle[1..].to_vec();
+        key.sort();
+        seen.insert(key)
+    });
+    cycles
 }
 
 static ALIAS_FIRST_LINE_REGEX: LazyLock<regex::Regex> = LazyLock::new(|| {
-    regex::Regex::new(r"^[ \t]*---@alias[ \t]+(?<types>.*)[ \t]*(#|--)?").unwrap()
+    regex::Regex::new(
+        &with_prefix(r"^[ \t]*---[ \t]*@alias[ \t]+(?<name>\w+)([ \t]+(?<types>[^#]*?))?[ \t]*((#|--)[ \t]*(?<desc>.*))?$"),
+    )
+    .unwrap()
 });
 
 static ALIAS_OTHER_LINE_REGEX: LazyLock<regex::Regex> = LazyLock::new(|| {
-    regex::Regex::new(r"^[ \t]*---[ \t]*\|[ \t]*(?<type>.*)[ \t]*(#|--)?").unwrap()
+    regex::Regex::new(&with_prefix(
+        r"^[ \t]*---[ \t]*\|[>+]?[ \t]*(?<type>.*?)[ \t]*((#|--)[ \t]*(?<desc>.*))?$",
+    ))
+    .unwrap()
 });
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(block: &str) -> Vec<Attribute> {
+        let lines = block
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (line, i + 1))
+            .collect::<Vec<_>>();
+        let (_, attributes) = attributes_from_comment_block(&lines);
+        attributes.into_iter().map(|attr| attr.value).collect()
+    }
+
+    /// The LDoc lines for the attributes in `block`.
+    fn ldoc(block: &str) -> Vec<String> {
+        params(block)
+            .iter()
+            .map(Attribute::to_ldoc_string)
+            .collect()
+    }
+
+    #[test]
+    fn descriptions_pass_through_untouched() {
+        assert_eq!(
+            ldoc("---@param side string? left | right, or {both}?\n---@return boolean ok is it done? a | b\n---@field mode string? fast | slow {default}"),
+            [
+                "---@tparam[opt] string side left | right, or {both}?",
+                "---@treturn boolean ok is it done? a | b",
+                "---@tfield string|nil mode fast | slow {default}",
+            ]
+        );
+    }
+
+    #[test]
+    fn parenthesized_types() {
+        assert_eq!(
+            ldoc("---@param a (Foo|Bar)[]\n---@param b (string)\n---@return (Foo|Bar)[]"),
+            [
+                "---@tparam {Foo|Bar,...} a",
+                "---@tparam string b",
+                "---@treturn {Foo|Bar,...}",
+            ]
+        );
+    }
+
+    #[test]
+    fn array_types() {
+        assert_eq!(
+            ldoc(
+                "---@param a string[]\n---@param b Foo[]\n---@param c (a|b)[]\n---@param d Foo[][]\n\
+                 ---@return string[]\n---@return Foo[]\n---@return (a|b)[]\n---@return Foo[][]"
+            ),
+            [
+                "---@tparam {string,...} a",
+                "---@tparam {Foo,...} b",
+                "---@tparam {a|b,...} c",
+                "---@tparam table d Type: `Foo[][]`.",
+                "---@treturn {string,...}",
+                "---@treturn {Foo,...}",
+                "---@treturn {a|b,...}",
+                "---@treturn table Type: `Foo[][]`.",
+            ]
+        );
+    }
+
+    #[test]
+    fn callbacks_keep_their_signature() {
+        assert_eq!(
+            ldoc("---@param cb fun(err: string?, result: T): boolean, string The callback"),
+            ["---@tparam function cb The callback (Type: `fun(err: string?, result: T): boolean, string`.)"]
+        );
+    }
+
+    #[test]
+    fn returns_without_a_name() {
+        assert_eq!(
+            ldoc("---@return boolean # ok\n---@return number count\n---@return string#the name"),
+            [
+                "---@treturn boolean ok",
+                "---@treturn number count",
+                "---@treturn string the name",
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_is_any() {
+        assert_eq!(
+            ldoc("---@param a unknown\n---@param b unknown?\n---@param c unknown|string\n---@param d any"),
+            [
+                "---@tparam any a",
+                "---@tparam[opt] any b",
+                "---@tparam any|string c",
+                "---@tparam any d",
+            ]
+        );
+        assert_eq!(format_type("unknownish"), "unknownish");
+    }
+
+    #[test]
+    fn param_defaults() {
+        assert_eq!(
+            ldoc(
+                "---@param a number default: 5\n---@param b string default: \"foo\" The name\n\
+                 ---@param c string? The plain one\n---@param d number default = 7\n\
+                 ---@param e number The count, default: 3"
+            ),
+            [
+                "---@tparam[opt=5] number a",
+                "---@tparam[opt=\"foo\"] string b The name",
+                "---@tparam[opt] string c The plain one",
+                "---@tparam[opt=7] number d",
+                "---@tparam number e The count, default: 3",
+            ]
+        );
+    }
+
+    #[test]
+    fn number_and_boolean_literals() {
+        assert_eq!(
+            ldoc(
+                "---@param mode 1|2|3\n---@param flag true|false\n---@param x 1.5|2\n\
+                 ---@param y -1|nil\n---@return true"
+            ),
+            [
+                "---@tparam integer mode One of: `1`, `2`, `3`.",
+                "---@tparam boolean flag",
+                "---@tparam number x One of: `1.5`, `2`.",
+                "---@tparam[opt] integer y One of: `-1`.",
+                "---@treturn boolean One of: `true`.",
+            ]
+        );
+    }
+
+    #[test]
+    fn single_quoted_literals() {
+        assert_eq!(
+            ldoc("---@param m 'a'|'b'\n---@return 'ok'|nil"),
+            [
+                "---@tparam string m One of: `a`, `b`.",
+                "---@treturn string|nil One of: `ok`.",
+            ]
+        );
+    }
+
+    #[test]
+    fn multiline_aliases() {
+        let mut source = "---@alias Mode\n---| \"r\" # Read\n---|   'w'\n---|> number # A descriptor\nlocal x = 1\n"
+            .to_string();
+        let aliases = extract_alias(&mut source);
+        assert_eq!(source, "\n\n\n\nlocal x = 1\n");
+        let [Attribute::Alias { name, members }] = aliases.as_slice() else {
+            panic!("{aliases:?}");
+        };
+        assert_eq!(name, "Mode");
+        assert_eq!(
+            members
+                .iter()
+                .map(|member| (member.ty.as_str(), member.desc.as_deref()))
+                .collect::<Vec<_>>(),
+            [
+                ("\"r\"", Some("Read")),
+                ("'w'", None),
+                ("number", Some("A descriptor"))
+            ]
+        );
+    }
+
+    #[test]
+    fn comment_blocks_without_a_syntax_tree() {
+        let lines = [
+            ("---Adds one.", 10),
+            ("---", 11),
+            ("---Works on integers.", 12),
+            ("---@param x integer The number", 13),
+            ("---@return integer # One more", 14),
+        ];
+        let (body, attributes) = attributes_from_comment_block(&lines);
+        assert_eq!(body, ["---Adds one.", "---", "---Works on integers."]);
+        assert_eq!(
+            attributes
+                .iter()
+                .map(|attr| (attr.value.to_ldoc_string(), attr.line))
+                .collect::<Vec<_>>(),
+            [
+                ("---@tparam integer x The number".to_string(), Some(13)),
+                ("---@treturn integer One more".to_string(), Some(14)),
+            ]
+        );
+    }
+
+    #[test]
+    fn params_keep_their_order() {
+        let attrs = params("---@param a string\n---@param b number\n---@param ... any The rest");
+        let names = attrs
+            .iter()
+            .filter_map(|attr| match attr {
+                Attribute::Param { name, .. } => Some(name.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["a", "b", "..."]);
+    }
+
+    #[test]
+    fn union_value_lines_fold_into_the_type() {
+        let attrs = params("---@param mode string The mode\n---| \"fast\" # Quick\n---|> \"slow\"");
+        let [Attribute::Param { ty, desc, .. }] = attrs.as_slice() else {
+            panic!("{attrs:?}");
+        };
+        assert_eq!(ty, r#"string|"fast"|"slow""#);
+        assert_eq!(desc.as_deref(), Some("The mode; `fast`: Quick"));
+    }
+
+    #[test]
+    fn union_value_lines_need_an_attribute_right_before() {
+        let attrs = params("---Text.\n---| \"fast\"");
+        assert!(attrs.is_empty(), "{attrs:?}");
+    }
+
+    #[test]
+    fn generic_types() {
+        assert_eq!(format_type("table<string, number>"), "table");
+        assert_eq!(
+            format_type("table<string, fun(a: number): boolean>|nil"),
+            "table|nil"
+        );
+        assert_eq!(format_type("table<fun(a: number), string>"), "table");
+        assert_eq!(format_type("table<string, Foo>[]"), "table");
+        assert_eq!(format_type("Container<T>"), "Container");
+    }
+
+    #[test]
+    fn arrays_of_generic_tables() {
+        for ty in [
+            "table<string, Foo>[]",
+            "(table<string, Foo>)[]",
+            "table<string, fun(a: number): boolean>[]",
+            "table<string, number>[][]",
+        ] {
+            assert_eq!(format_type(ty), "table", "{ty}");
+        }
+        assert_eq!(format_type("table<string, Foo>[]|nil"), "table|nil");
+        assert_eq!(format_type("Foo[]|table<string, Foo>[]"), "{Foo,...}|table");
+    }
+
+    #[test]
+    fn generic_types_keep_their_type_in_the_description() {
+        assert_eq!(
+            with_type_note("table<fun(a: number), string>", None).as_deref(),
+            Some("Type: `table<fun(a:number),string>`.")
+        );
+        assert_eq!(
+            with_type_note("table<string, Foo>[]", Some("The foos")).as_deref(),
+            Some("The foos (Type: `table<string,Foo>[]`.)")
+        );
+    }
+
+    #[test]
+    fn function_types_in_unions() {
+        assert_eq!(format_type("fun(x: number): string|nil"), "function");
+        assert_eq!(
+            format_type("fun(x: number)|string[]"),
+            "function|{string,...}"
+        );
+        assert_eq!(
+            format_type("(fun(x: number): boolean)[]|nil"),
+            "{function,...}|nil"
+        );
+        assert_eq!(format_type("Foo[][]"), "table");
+    }
+
+    #[test]
+    fn literal_unions() {
+        assert_eq!(
+            format_literal_union(r#""left"|"right""#, None),
+            (
+                "string".to_string(),
+                Some("One of: `left`, `right`.".to_string())
+            )
+        );
+        assert_eq!(
+            format_literal_union(r#"number|"auto""#, Some("How much")),
+            (
+                "number|string".to_string(),
+                Some("How much (Known values: `auto`.)".to_string())
+            )
+        );
+        assert_eq!(
+            format_literal_union(r#"string|"a"|nil"#, None),
+            (
+                "string|nil".to_string(),
+                Some("Known values: `a`.".to_string())
+            )
+        );
+        assert_eq!(
+            format_literal_union("true|false", None),
+            ("boolean".to_string(), None)
+        );
+    }
+
+    #[test]
+    fn examples_keep_their_relative_indentation() {
+        let mut source = [
+            "--- ### Example",
+            "--- ```lua",
+            "---\tif x then",
+            "---\t    for i = 1, 2 do",
+            "---            print(i)",
+            "---\t\tend",
+            "---",
+            "---    end",
+            "--- ```",
+            "function f() end",
+        ]
+        .join("\n");
+        replace_examples(&mut source, &example_regex(&["Example".to_string()]));
+        assert_eq!(
+            source,
+            [
+                "---@usage",
+                "--- if x then",
+                "---     for i = 1, 2 do",
+                "---         print(i)",
+                "---     end",
+                "---",
+                "--- end",
+                "function f() end",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn code_comments() {
+        for code in [
+            r#" print("x")"#,
+            " TODO fix",
+            " local x = 5",
+            " x = 5",
+            " if x then",
+            " for i = 1, 10 do",
+            " return x",
+        ] {
+            assert!(is_code_comment(code), "{code}");
+        }
+        for note in [
+            " A note that belongs to the description.",
+            " if you pass nil, nothing happens.",
+            " for example, a string",
+            " see the manual (chapter 2)",
+            " x == 5 is checked first",
+        ] {
+            assert!(!is_code_comment(note), "{note}");
+        }
+    }
+}
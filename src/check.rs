@@ -0,0 +1,312 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Checks that docs match the code: `@param` names against the parameters, duplicate
+//! `@param`s, `@return` counts against `return` statements, and coverage for `--check`.
+
+use std::collections::HashMap;
+
+use tree_sitter::Node;
+
+use crate::{attr::Attribute, chunk::Chunk, diag, Declaration, SourceFile};
+
+/// The parameter names in a function declaration or definition's signature,
+/// with `...` for varargs.
+pub fn function_params(node: Node, source: &[u8]) -> Vec<String> {
+    let Some(params) = node.child_by_field_name("parameters") else {
+        return Vec::new();
+    };
+
+    let mut cursor = params.walk();
+    params
+        .named_children(&mut cursor)
+        .filter_map(|param| match param.kind() {
+            "identifier" => param.utf8_text(source).ok().map(str::to_string),
+            "vararg_expression" => Some("...".to_string()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Whether a function declaration is a `local function`.
+pub fn is_local_function(node: Node) -> bool {
+    node.child(0).is_some_and(|child| child.kind() == "local")
+}
+
+/// Whether the body of a function returns a value, not counting nested functions.
+fn returns_value(node: Node) -> bool {
+    let mut cursor = node.walk();
+    let children = node.named_children(&mut cursor).collect::<Vec<_>>();
+    children.into_iter().any(|child| match child.kind() {
+        "return_statement" => child.named_child_count() > 0,
+        "function_declaration" | "function_definition" => false,
+        _ => returns_value(child),
+    })
+}
+
+/// The `return` statements in a function body that return values, not counting nested
+/// functions, as (line, number of values, whether the last one can be any number of them).
+/// A call or `...` at the end can be.
+fn return_counts(node: Node, counts: &mut Vec<(usize, usize, bool)>) {
+    let mut cursor = node.walk();
+    let children = node.named_children(&mut cursor).collect::<Vec<_>>();
+    for child in children {
+        match child.kind() {
+            "return_statement" => {
+                let Some(list) = child.named_child(0) else {
+                    continue;
+                };
+                let mut cursor = list.walk();
+                let values = list
+                    .named_children(&mut cursor)
+                    .filter(|value| value.kind() != "comment")
+                    .collect::<Vec<_>>();
+                let open = values.last().is_some_and(|value| {
+                    matches!(value.kind(), "function_call" | "vararg_expression")
+                });
+                counts.push((child.range().start_point.row + 1, values.len(), open));
+            }
+            "function_declaration" | "function_definition" => (),
+            _ => return_counts(child, counts),
+        }
+    }
+}
+
+/// Warn about `return` statements that return a different number of values than the
+/// function has `@return`s, which usually means the docs are out of date or out of order.
+/// Functions without any `@return`s, or with a variadic one, aren't checked.
+pub fn check_return_counts(file: &SourceFile, chunks: &[Chunk]) {
+    let source = file.contents.as_bytes();
+
+    for chunk in chunks.iter() {
+        let Declaration::Function(_, node) = &chunk.decl else {
+            continue;
+        };
+        let returns = chunk
+            .attrs()
+            .filter_map(|attr| match attr {
+                Attribute::Return { name, .. } => Some(name.as_deref()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        if returns.is_empty()
+            || returns.contains(&Some("..."))
+            || chunk.attrs().any(|attr| matches!(attr, Attribute::NoDoc))
+        {
+            continue;
+        }
+
+        let name = node
+            .child_by_field_name("name")
+            .and_then(|name| name.utf8_text(source).ok())
+            .unwrap_or("<anonymous>");
+        let mut counts = Vec::new();
+        if let Some(body) = node.child_by_field_name("body") {
+            return_counts(body, &mut counts);
+        }
+
+        for (line, count, open) in counts {
+            // A bare `return` just ends the function early
+            let matches =
+                count == 0 || count == returns.len() || (open && count - 1 <= returns.len());
+            if !matches {
+                diag::warn(
+                    &file.path,
+                    Some(line),
+                    diag::Kind::ReturnMismatch,
+                    &format!(
+                        "function {name} returns {count} value(s) but has {} @return(s)",
+                        returns.len()
+                    ),
+                );
+            }
+        }
+    }
+}
+
+/// Report `@param`s that don't name one of the function's parameters, which usually means
+/// the parameter was renamed. If `undocumented` is set, parameters without a `@param` are
+/// reported too, for functions that document at least one. These are warnings, or errors
+/// if `strict` is set.
+///
+/// Returns the number of mismatches found.
+pub fn check_param_names(
+    file: &SourceFile,
+    chunks: &[Chunk],
+    undocumented: bool,
+    strict: bool,
+) -> usize {
+    let source = file.contents.as_bytes();
+    let report = if strict { diag::error } else { diag::warn };
+    let mut mismatches = 0;
+
+    for chunk in chunks.iter() {
+        let Declaration::Function(_, node) = &chunk.decl else {
+            continue;
+        };
+        if chunk.attrs().any(|attr| matches!(attr, Attribute::NoDoc)) {
+            continue;
+        }
+
+        let name = node
+            .child_by_field_name("name")
+            .and_then(|name| name.utf8_text(source).ok())
+            .unwrap_or("<anonymous>");
+        let line = node.range().start_point.row + 1;
+        let mut params = function_params(*node, source);
+        // Methods get an implicit `self`
+        if name.contains(':') {
+            params.push("self".to_string());
+        }
+
+        // (name, line of the @param)
+        let documented = chunk
+            .attributes
+            .iter()
+            .filter_map(|attr| match &attr.value {
+                // `opts.timeout` documents a field of `opts`
+                Attribute::Param { name, .. } if name == "..." => Some((name.as_str(), attr.line)),
+                Attribute::Param { name, .. } => Some((name.split('.').next()?, attr.line)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        for (param, param_line) in documented.iter() {
+            if !params.iter().any(|real| real == param) {
+                mismatches += 1;
+                report(
+                    &file.path,
+                    Some(param_line.unwrap_or(line)),
+                    diag::Kind::ParamMismatch,
+                    &format!("function {name} has a @param for '{param}' but no such parameter"),
+                );
+            }
+        }
+
+        if !undocumented || documented.is_empty() {
+            continue;
+        }
+        // `_` is an ignored parameter and doesn't need documenting
+        for param in params
+            .iter()
+            .filter(|param| *param != "self" && *param != "_")
+        {
+            if !documented.iter().any(|(name, _)| name == param) {
+                mismatches += 1;
+                report(
+                    &file.path,
+                    Some(line),
+                    diag::Kind::ParamMismatch,
+                    &format!("function {name} has no @param for parameter '{param}'"),
+                );
+            }
+        }
+    }
+
+    mismatches
+}
+
+/// Report `@param`s documented more than once in the same chunk, dropping all but the first
+/// unless `keep` is set. Repeated `_`s are different ignored parameters, so they're fine.
+///
+/// Returns the number of duplicates found.
+pub fn check_duplicate_params(
+    file: &SourceFile,
+    chunks: &mut [Chunk],
+    keep: bool,
+    strict: bool,
+) -> usize {
+    let report = if strict { diag::error } else { diag::warn };
+    let mut duplicates = 0;
+
+    for chunk in chunks.iter_mut() {
+        // Line of the first @param for each name
+        let mut seen = HashMap::<String, Option<usize>>::new();
+        chunk.attributes.retain(|attr| {
+            let Attribute::Param { name, .. } = &attr.value else {
+                return true;
+            };
+            if name == "_" {
+                return true;
+            }
+            let Some(first) = seen.get(name) else {
+                seen.insert(name.clone(), attr.line);
+                return true;
+            };
+            duplicates += 1;
+            let first = match first {
+                Some(line) => format!(", first on line {line}"),
+                None => String::new(),
+            };
+            report(
+                &file.path,
+                attr.line,
+                diag::Kind::DuplicateParam,
+                &format!("@param '{name}' is documented more than once{first}"),
+            );
+            keep
+        });
+    }
+
+    duplicates
+}
+
+/// Report documented, non-local functions that are missing a `@param` for a parameter
+/// or a `@return` when they return something.
+///
+/// Returns the number of gaps found.
+pub fn check_coverage(file: &SourceFile, chunks: &[Chunk]) -> usize {
+    let source = file.contents.as_bytes();
+    let mut gaps = 0;
+
+    for chunk in chunks.iter() {
+        let Declaration::Function(_, node) = &chunk.decl else {
+            continue;
+        };
+        if is_local_function(*node) || chunk.attrs().any(|attr| matches!(attr, Attribute::NoDoc)) {
+            continue;
+        }
+
+        let name = node
+            .child_by_field_name("name")
+            .and_then(|name| name.utf8_text(source).ok())
+            .unwrap_or("<anonymous>");
+        let line = node.range().start_point.row + 1;
+
+        for param in function_params(*node, source) {
+            // `self` is implied for methods and usually not documented, and `_` is ignored
+            if param == "self" || param == "_" {
+                continue;
+            }
+            let documented = chunk
+                .attrs()
+                .any(|attr| matches!(attr, Attribute::Param { name, .. } if *name == param));
+            if !documented {
+                gaps += 1;
+                diag::error(
+                    &file.path,
+                    Some(line),
+                    diag::Kind::MissingDoc,
+                    &format!("function {name} missing @param for '{param}'"),
+                );
+            }
+        }
+
+        let has_return = chunk
+            .attrs()
+            .any(|attr| matches!(attr, Attribute::Return { .. }));
+        let returns = node.child_by_field_name("body").is_some_and(returns_value);
+        if returns && !has_return {
+            gaps += 1;
+            diag::error(
+                &file.path,
+                Some(line),
+                diag::Kind::MissingDoc,
+                &format!("function {name} missing @return"),
+            );
+        }
+    }
+
+    gaps
+}
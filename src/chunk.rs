@@ -4,69 +4,944 @@
 
 use tree_sitter::Node;
 
-use crate::{attr::Attribute, Declaration};
+use crate::{
+    attr::{doc_prefix, is_url, module_path, Attribute, Spanned, Visibility, ATTR_REGEXES},
+    check::{function_params, is_local_function},
+    first_target, first_value, is_identifier,
+    render::Renderer,
+    strings::{self, Text},
+    Args, Declaration, SelfStyle,
+};
 
 #[derive(Debug)]
 pub struct Chunk<'a> {
     /// The summary bits
-    pub body: Vec<Node<'a>>,
-    /// The bits that start with ---@attr
-    pub attributes: Vec<Attribute>,
+    pub body: Vec<String>,
+    /// The bits that start with ---@attr, in source order.
+    ///
+    /// LDoc matches `@tparam`s to parameters by position, so params (including `...`)
+    /// must be emitted in the order they were documented.
+    pub attributes: Vec<Spanned<Attribute>>,
     /// The thing being annotated
     pub decl: Declaration<'a>,
 }
 
 impl Chunk<'_> {
-    pub fn to_ldoc_string(&self, source: &[u8]) -> String {
+    /// The attributes without their lines.
+    pub fn attrs(&self) -> impl Iterator<Item = &Attribute> {
+        self.attributes.iter().map(|attr| &attr.value)
+    }
+
+    /// The attributes without their lines, mutably.
+    pub fn attrs_mut(&mut self) -> impl Iterator<Item = &mut Attribute> {
+        self.attributes.iter_mut().map(|attr| &mut attr.value)
+    }
+
+    /// Whether this chunk declares a module or class.
+    pub fn is_class(&self) -> bool {
+        self.attrs()
+            .any(|attr| matches!(attr, Attribute::Class { .. }))
+    }
+
+    /// Whether this is a `local` function, or a member marked `@private` or `@package`, which
+    /// LDoc keeps out of the public API as `@local`.
+    pub fn is_local(&self) -> bool {
+        let is_local_function = match self.decl {
+            Declaration::Function(_, node) => is_local_function(node),
+            Declaration::Variable(_, decl) => {
+                decl.kind() == "variable_declaration"
+                    && (self.fun_type().is_some()
+                        || first_value(decl)
+                            .is_some_and(|value| value.kind() == "function_definition"))
+            }
+            _ => false,
+        };
+        is_local_function
+            || self
+                .attrs()
+                .any(|attr| matches!(attr, Attribute::Package | Attribute::Private))
+    }
+
+    /// Render as LDoc, with `renderer` rendering each attribute. `class` is the class the
+    /// chunk is grouped under, if any.
+    pub fn to_ldoc_string(
+        &self,
+        source: &[u8],
+        class: Option<&str>,
+        renderer: &dyn Renderer,
+        args: &Args,
+    ) -> String {
         let mut ret = String::new();
         ret.push('\n');
 
-        for node in self.body.iter() {
-            let comment = node.utf8_text(source).unwrap();
-            ret.push_str(comment);
+        let mut body = self.normalized_body();
+        // With --explicit-summary the summary becomes an @summary after the description
+        let mut summary = None;
+        if args.explicit_summary {
+            if let Some(first) = body.iter().position(|line| !is_blank_doc_line(line)) {
+                let end = summary_end(&body);
+                let lines = body.drain(..end).skip(first).collect::<Vec<_>>();
+                summary = Some(
+                    lines
+                        .iter()
+                        .map(|line| line.trim_start_matches('-').trim())
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                );
+                while body.first().is_some_and(|line| is_blank_doc_line(line)) {
+                    body.remove(0);
+                }
+            }
+        }
+
+        let wrap = |line: &str, keep: usize| match args.wrap {
+            Some(width) => wrap_line(line, width, keep).join("\n"),
+            None => line.to_string(),
+        };
+
+        // The first paragraph is the summary and the rest is the description.
+        // LDoc wants a blank line between the two.
+        let summary_end = summary_end(&body);
+        let mut in_fence = false;
+        for (i, comment) in body.iter().enumerate() {
+            let comment = comment.as_str();
+            if i == summary_end && summary.is_none() && !is_blank_doc_line(comment) {
+                ret.push_str("---\n");
+            }
+            let text = comment.strip_prefix("---").unwrap_or(comment);
+            if text.trim_start().starts_with("```") {
+                in_fence = !in_fence;
+            }
+            // Code and leftover tags would change meaning if they were wrapped
+            let prose =
+                !in_fence && !text.starts_with("  ") && !text.trim_start().starts_with(['@', '`']);
+            if prose {
+                ret.push_str(&wrap(comment, 0));
+            } else {
+                ret.push_str(comment);
+            }
             ret.push('\n');
         }
 
-        for attr in self.attributes.iter() {
-            if let Attribute::ClassMod = attr {
+        if let Some(generics) = self.generics() {
+            ret.push_str(&format!("---\n---{}\n", generics));
+        }
+
+        // LDoc has no @nodiscard, so just leave a note in the description
+        if self
+            .attrs()
+            .any(|attr| matches!(attr, Attribute::Nodiscard))
+        {
+            ret.push_str(&format!("---\n---{}\n", strings::get(Text::Nodiscard)));
+        }
+
+        // Or @deprecated
+        if let Some(note) = self.deprecation_note() {
+            ret.push_str(&format!("---\n---{note}\n"));
+        }
+
+        if let Some(op) = self.metamethod(source) {
+            ret.push_str(&format!(
+                "---\n---{} {op}.\n",
+                strings::get(Text::Metamethod)
+            ));
+        }
+
+        // @source has no LDoc equivalent either, and neither do links to URLs
+        for attr in self.attrs() {
+            let in_description = match attr {
+                Attribute::Source { .. } => true,
+                Attribute::See { link, .. } => is_url(link) && !args.inline_see,
+                _ => false,
+            };
+            if in_description {
+                ret.push_str("---\n");
+                ret.push_str(&renderer.render_attribute(attr));
+                ret.push('\n');
+            }
+        }
+
+        if args.inline_see {
+            if let Some(see) = self.inline_see() {
+                ret.push_str(&format!("---\n{see}\n"));
+            }
+        }
+
+        if let Some(summary) = summary {
+            ret.push_str(&wrap(&format!("---@summary {summary}"), 1));
+            ret.push('\n');
+        }
+
+        let method = self.method(source);
+
+        if let (SelfStyle::Dot, Some(method)) = (args.self_style, &method) {
+            let documented = self
+                .attrs()
+                .any(|attr| matches!(attr, Attribute::Param { name, .. } if name == "self"));
+            if !documented {
+                ret.push_str(&format!("---@tparam {} self\n", method.table));
+            }
+        }
+
+        let mut attributes = self.attrs().collect::<Vec<_>>();
+        if args.normalize_order {
+            // Stable, so params and returns keep their source order
+            attributes.sort_by_key(|attr| ldoc_order(attr));
+        }
+        // LDoc lists what a function raises after what it returns
+        if let Some(last_return) = attributes
+            .iter()
+            .rposition(|attr| matches!(attr, Attribute::Return { .. }))
+        {
+            let (raises, rest): (Vec<_>, Vec<_>) = attributes
+                .into_iter()
+                .enumerate()
+                .partition(|(i, attr)| *i < last_return && matches!(attr, Attribute::Raise { .. }));
+            let at = rest.iter().position(|(i, _)| *i == last_return).unwrap() + 1;
+            attributes = rest.into_iter().map(|(_, attr)| attr).collect();
+            attributes.splice(at..at, raises.into_iter().map(|(_, attr)| attr));
+        }
+
+        for attr in attributes {
+            if let (SelfStyle::Colon, Some(_), Attribute::Param { name, .. }) =
+                (args.self_style, &method, attr)
+            {
+                // `self` is implied by the colon
+                if name == "self" {
+                    continue;
+                }
+            }
+
+            if let Attribute::ClassMod { .. }
+            | Attribute::Nodiscard
+            | Attribute::Deprecated { .. }
+            | Attribute::Since { .. }
+            | Attribute::Version { .. }
+            | Attribute::Source { .. } = attr
+            {
+                continue;
+            } else if matches!(attr, Attribute::See { link, .. } if is_url(link) || args.inline_see)
+            {
+                // Already in the description
+                continue;
+            } else if self.usage_in_examples(attr, args) {
+                // The example it repeats is already turned into a @usage
                 continue;
-            } else if let Attribute::Class { ty } = attr {
+            } else if let Attribute::FunType { params, returns } = attr {
+                // Explicit @params and @returns win over the ones from the type
+                let documented = |param: &str| {
+                    self.attrs()
+                        .any(|attr| matches!(attr, Attribute::Param { name, .. } if name == param))
+                };
+                let has_returns = self
+                    .attrs()
+                    .any(|attr| matches!(attr, Attribute::Return { .. }));
+                let fun_type = Attribute::FunType {
+                    params: params
+                        .iter()
+                        .filter(|(name, _)| !documented(name))
+                        .cloned()
+                        .collect(),
+                    returns: if has_returns { vec![] } else { returns.clone() },
+                };
+                let fun_type = renderer.render_attribute(&fun_type);
+                if fun_type.is_empty() {
+                    continue;
+                }
+                ret.push_str(&fun_type);
+            } else if let Attribute::Field { visibility, .. } = attr {
+                if *visibility != Visibility::Public && !args.include_private {
+                    continue;
+                }
+                if attr.fun_type().is_some() && self.is_class() {
+                    // Rendered as functions after the class, see below
+                    continue;
+                }
+                ret.push_str(&renderer.render_attribute(attr));
+            } else if let Attribute::Class { ty, .. } = attr {
                 // println!("got class {ty}");
-                if self
-                    .attributes
-                    .iter()
-                    .any(|a| matches!(a, Attribute::ClassMod))
-                {
-                    // println!("pushing ---@classmod {ty}");
-                    ret.push_str(&format!("---@classmod {ty}"));
-                } else {
-                    // println!("pushing {}", attr.to_ldoc_string());
-                    ret.push_str(&attr.to_ldoc_string());
+                let classmod = self.attrs().find_map(|a| match a {
+                    Attribute::ClassMod { is_module } => Some(*is_module),
+                    _ => None,
+                });
+                match classmod {
+                    Some(true) => ret.push_str(&format!("---@classmod {}", module_path(ty))),
+                    Some(false) => ret.push_str(&format!("---@type {ty}")),
+                    None => ret.push_str(&renderer.render_attribute(attr)),
                 }
+            } else if let Attribute::Return { ty, name, desc } = attr {
+                let ret_attr = Attribute::Return {
+                    ty: self.return_type(ty, class),
+                    name: name.clone(),
+                    desc: desc.clone(),
+                };
+                ret.push_str(&wrap(&renderer.render_attribute(&ret_attr), 2));
+            } else if let Attribute::Param { .. } = attr {
+                ret.push_str(&wrap(&renderer.render_attribute(attr), 3));
             } else {
                 // println!("pushing {}", attr.to_ldoc_string());
-                ret.push_str(&attr.to_ldoc_string());
+                ret.push_str(&renderer.render_attribute(attr));
             }
             ret.push('\n');
         }
 
+        let tagged_local = self
+            .attrs()
+            .any(|attr| matches!(attr, Attribute::Package | Attribute::Private));
+        if self.is_local() && !tagged_local {
+            ret.push_str("---@local\n");
+        }
+
+        // Version info goes after everything else, @since first
+        let since = self
+            .attrs()
+            .filter(|attr| matches!(attr, Attribute::Since { .. }));
+        let version = self
+            .attrs()
+            .filter(|attr| matches!(attr, Attribute::Version { .. }));
+        for attr in since.chain(version) {
+            ret.push_str(&renderer.render_attribute(attr));
+            ret.push('\n');
+        }
+
+        // LDoc can't find the name in `M["some-key"] = function() end`, so give it one
+        if let Some(name) = self.string_key_function(source) {
+            ret.push_str(&format!("---@function {name}\n"));
+        }
+
+        let decl = self.decl_text(source, args);
+
+        if !decl.is_empty() {
+            ret.push_str(&decl);
+            ret.push('\n');
+        }
+
+        if let Some(class) = self.class_name() {
+            ret.push_str(&self.function_fields(class, args));
+        }
+
+        ret
+    }
+
+    /// The declaration, with function bodies removed and methods written in `--self-style`.
+    pub fn decl_text(&self, source: &[u8], args: &Args) -> String {
+        let method = self.method(source);
+
         let decl = match self.decl {
             Declaration::Function(_, decl) => {
-                let ret = decl.utf8_text(source).unwrap();
-                if let Some(body) = decl.child_by_field_name("body") {
-                    ret.replace(body.utf8_text(source).unwrap(), "")
-                } else {
-                    ret.to_string()
+                let mut ret = decl.utf8_text(source).unwrap().to_string();
+                let range = |node: Node| {
+                    node.start_byte() - decl.start_byte()..node.end_byte() - decl.start_byte()
+                };
+
+                // Replace by byte ranges instead of by text so we don't clobber identical
+                // text elsewhere in the declaration. Later ranges go first so earlier
+                // ones stay valid.
+                if let Some(body) = body_range(decl) {
+                    ret.replace_range(
+                        body.start - decl.start_byte()..body.end - decl.start_byte(),
+                        " end",
+                    );
+                }
+
+                if let Some(method) = &method {
+                    let params = method.params.clone();
+                    let (sep, params) = match args.self_style {
+                        SelfStyle::Keep => (None, None),
+                        SelfStyle::Colon => (Some(':'), Some(params)),
+                        SelfStyle::Dot => {
+                            let mut params = params;
+                            params.insert(0, "self".to_string());
+                            (Some('.'), Some(params))
+                        }
+                    };
+                    if let (Some(params), Some(node)) =
+                        (params, decl.child_by_field_name("parameters"))
+                    {
+                        ret.replace_range(range(node), &format!("({})", params.join(", ")));
+                    }
+                    if let (Some(sep), Some(name)) = (sep, decl.child_by_field_name("name")) {
+                        ret.replace_range(
+                            range(name),
+                            &format!("{}{sep}{}", method.table, method.name),
+                        );
+                    }
                 }
+
+                ret
+            }
+            Declaration::Variable(ref name, decl)
+                if self.fun_type().is_some() && !name.contains('[') =>
+            {
+                // Write it as a function so LDoc documents it as one
+                let params = self
+                    .fun_type()
+                    .into_iter()
+                    .flatten()
+                    .map(|(name, _)| name.as_str())
+                    .collect::<Vec<_>>();
+                format!(
+                    "{}function {name}({}) end",
+                    if decl.kind() == "variable_declaration" {
+                        "local "
+                    } else {
+                        ""
+                    },
+                    params.join(", ")
+                )
+            }
+            Declaration::Variable(ref name, decl) => {
+                let mut ret = decl.utf8_text(source).unwrap().to_string();
+                let range = |node: Node| {
+                    node.start_byte() - decl.start_byte()..node.end_byte() - decl.start_byte()
+                };
+
+                // `M.f = function() ... end` only needs the signature. This is the outermost
+                // function's body, so functions it returns go with it.
+                if let Some(body) = first_value(decl)
+                    .filter(|value| value.kind() == "function_definition")
+                    .and_then(body_range)
+                {
+                    ret.replace_range(
+                        body.start - decl.start_byte()..body.end - decl.start_byte(),
+                        " end",
+                    );
+                }
+
+                // Write `T["key"]` as `T.key` so LDoc picks up the name
+                if let Some(target) = first_target(decl) {
+                    if target.kind() == "bracket_index_expression" && !name.contains('[') {
+                        ret.replace_range(range(target), name);
+                    }
+                }
+                ret
             }
-            Declaration::Variable(_, decl) | Declaration::Other(decl) => {
-                decl.utf8_text(source).unwrap().to_string()
+            Declaration::Other(decl) => decl.utf8_text(source).unwrap().to_string(),
+            Declaration::Dangling => String::new(),
+            Declaration::Target(_, target, value) => {
+                let is_local = target
+                    .parent()
+                    .and_then(|var_list| var_list.parent())
+                    .is_some_and(|stmt| {
+                        stmt.kind() == "variable_declaration"
+                            || stmt
+                                .parent()
+                                .is_some_and(|parent| parent.kind() == "variable_declaration")
+                    });
+                let value = match value {
+                    Some(value) => {
+                        let mut text = value.utf8_text(source).unwrap().to_string();
+                        if let Some(body) =
+                            body_range(value).filter(|_| value.kind() == "function_definition")
+                        {
+                            text.replace_range(
+                                body.start - value.start_byte()..body.end - value.start_byte(),
+                                " end",
+                            );
+                        }
+                        text
+                    }
+                    // `local b` in `local a, b = f()`, since it's one of f()'s other returns
+                    None if is_local => {
+                        return format!("local {}", target.utf8_text(source).unwrap());
+                    }
+                    // There's nothing better to show for `a, b = f()` than all of it
+                    None => target
+                        .parent()
+                        .and_then(|var_list| var_list.next_named_sibling())
+                        .filter(|values| values.kind() == "expression_list")
+                        .filter(|values| {
+                            values
+                                .named_child(values.named_child_count().saturating_sub(1))
+                                .is_some_and(|last| {
+                                    matches!(last.kind(), "function_call" | "vararg_expression")
+                                })
+                        })
+                        .and_then(|values| values.utf8_text(source).ok())
+                        .unwrap_or("nil")
+                        .to_string(),
+                };
+                format!(
+                    "{}{} = {value}",
+                    if is_local { "local " } else { "" },
+                    target.utf8_text(source).unwrap(),
+                )
             }
         };
 
-        ret.push_str(&decl);
-        ret.push('\n');
+        // `--[[@as Type]]` casts are for LuaLS only
+        ATTR_REGEXES.inline_cast.replace_all(&decl, "").to_string()
+    }
+
+    /// The key of a function assigned to a key that isn't an identifier, like `some-key` for
+    /// `M["some-key"] = function() end`, or one with a `@type fun(...)`.
+    fn string_key_function(&self, source: &[u8]) -> Option<String> {
+        let Declaration::Variable(name, decl) = &self.decl else {
+            return None;
+        };
+        let is_function = self.fun_type().is_some()
+            || first_value(*decl).is_some_and(|value| value.kind() == "function_definition");
+        if !name.contains('[') || !is_function {
+            return None;
+        }
+        self.decl.member_name(source)
+    }
+
+    /// Render the function-typed `@field`s of a class as functions on it, so LDoc
+    /// shows their signatures.
+    fn function_fields(&self, class: &str, args: &Args) -> String {
+        let mut ret = String::new();
+
+        for attr in self.attrs() {
+            let Attribute::Field {
+                name,
+                desc,
+                visibility,
+                ..
+            } = attr
+            else {
+                continue;
+            };
+            if *visibility != Visibility::Public && !args.include_private {
+                continue;
+            }
+            let Some(Attribute::FunType {
+                mut params,
+                returns,
+            }) = attr.fun_type()
+            else {
+                continue;
+            };
+
+            // fun(self, ...) is a method
+            let is_method = params.first().is_some_and(|(param, _)| param == "self");
+            // Keywords like `end` can only be keys as `Foo["end"]`, which can't take a colon
+            let is_keyword = !is_identifier(name);
+            let sep = match (is_method, args.self_style) {
+                _ if is_keyword => '.',
+                (false, _) | (true, SelfStyle::Dot) => '.',
+                (true, SelfStyle::Keep | SelfStyle::Colon) => {
+                    params.remove(0);
+                    ':'
+                }
+            };
+
+            ret.push('\n');
+            if let Some(desc) = desc {
+                ret.push_str(&format!("---{desc}\n"));
+            }
+            let names = params
+                .iter()
+                .map(|(name, _)| name.clone())
+                .collect::<Vec<_>>();
+            let fun_type = Attribute::FunType { params, returns }.to_ldoc_string();
+            if !fun_type.is_empty() {
+                ret.push_str(&fun_type);
+                ret.push('\n');
+            }
+            if is_keyword {
+                // Like `M["some-key"] = function() end`, LDoc can't find the name
+                ret.push_str(&format!("---@function {name}\n"));
+                ret.push_str(&format!(
+                    "{class}[\"{name}\"] = function({}) end\n",
+                    names.join(", ")
+                ));
+            } else {
+                ret.push_str(&format!(
+                    "function {class}{sep}{name}({}) end\n",
+                    names.join(", ")
+                ));
+            }
+        }
 
         ret
     }
+
+    /// The declared name, or for a `@class` with no code under it, the class name.
+    pub fn name(&self) -> Option<&str> {
+        match self.decl {
+            Declaration::Dangling => self.class_name(),
+            _ => self.decl.name(),
+        }
+    }
+
+    /// The body as `---` lines with consistent indentation, whatever the doc prefix and
+    /// mix of tabs and spaces in the source.
+    ///
+    /// A tab right after the `---` counts as one space and other tabs as four.
+    /// Lines in code fences are left as they are.
+    ///
+    /// Empty doc lines separate paragraphs, so each run of them becomes a single `---`,
+    /// and ones at the start or end are dropped.
+    pub fn normalized_body(&self) -> Vec<String> {
+        let mut in_fence = false;
+        let mut body = Vec::<String>::new();
+        for line in self.body.iter() {
+            let line = line.trim();
+            let blank = !in_fence
+                && line
+                    .strip_prefix(doc_prefix())
+                    .is_some_and(|rest| rest.trim().is_empty());
+            if blank {
+                if body.last().is_some_and(|last| last != "---") {
+                    body.push("---".to_string());
+                }
+                continue;
+            }
+            body.push(Self::normalize_line(line, &mut in_fence));
+        }
+        if body.last().is_some_and(|last| last == "---") {
+            body.pop();
+        }
+        body
+    }
+
+    /// One line of [`Chunk::normalized_body`], tracking whether it's in a code fence.
+    fn normalize_line(line: &str, in_fence: &mut bool) -> String {
+        // LDoc only knows `---`
+        let Some(rest) = line.strip_prefix(doc_prefix()) else {
+            return line.to_string();
+        };
+        if rest.trim_start().starts_with("```") {
+            *in_fence = !*in_fence;
+            return format!("---{}", rest.trim_start());
+        }
+        if *in_fence {
+            return format!("---{rest}");
+        }
+
+        let text = rest.trim_start();
+        let indent = &rest[..rest.len() - text.len()];
+        let mut indent = indent.chars();
+        let first = match indent.next() {
+            Some(_) => " ",
+            None => "",
+        };
+        let rest_indent = indent
+            .map(|c| if c == '\t' { "    " } else { " " })
+            .collect::<String>();
+        format!("---{first}{rest_indent}{text}")
+    }
+
+    /// A note listing the type parameters of the class this chunk declares, if it has any.
+    pub fn generics(&self) -> Option<String> {
+        let generics = self.attrs().find_map(|attr| match attr {
+            Attribute::Class { generics, .. } if !generics.is_empty() => Some(generics),
+            _ => None,
+        })?;
+        let generics = generics
+            .iter()
+            .map(|generic| format!("`{generic}`"))
+            .collect::<Vec<_>>();
+        Some(format!(
+            "{}: {}.",
+            strings::get(Text::TypeParams),
+            generics.join(", ")
+        ))
+    }
+
+    /// A note for the description if the chunk is `@deprecated`, with its message if it has one.
+    pub fn deprecation_note(&self) -> Option<String> {
+        let message = self.attrs().find_map(|attr| match attr {
+            Attribute::Deprecated { message } => Some(message.as_deref()),
+            _ => None,
+        })?;
+        Some(match message {
+            Some(message) => format!("{}: {message}", strings::get(Text::Deprecated)),
+            None => format!("{}.", strings::get(Text::Deprecated)),
+        })
+    }
+
+    /// All of the `@see`s as one "See also" line for the description, for `--inline-see`.
+    /// References are written as `@{ref}` so LDoc still links them.
+    fn inline_see(&self) -> Option<String> {
+        let see = self
+            .attrs()
+            .filter_map(|attr| match attr {
+                Attribute::See { link, desc } if is_url(link) => {
+                    Some(format!("[{}]({link})", desc.as_deref().unwrap_or(link)))
+                }
+                Attribute::See { link, .. } => Some(format!("@{{{link}}}")),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        if see.is_empty() {
+            return None;
+        }
+        Some(format!(
+            "---{}: {}",
+            strings::get(Text::SeeAlso),
+            see.join(", ")
+        ))
+    }
+
+    /// Whether `attr` is a `@usage` with the same code as one of the examples in the
+    /// description.
+    pub fn usage_in_examples(&self, attr: &Attribute, args: &Args) -> bool {
+        let Attribute::Usage { lines } = attr else {
+            return false;
+        };
+        let example_regex = crate::attr::example_regex(&args.example_headings);
+        crate::attr::example_codes(&self.body.join("\n"), &example_regex)
+            .contains(&crate::attr::usage_code(lines))
+    }
+
+    /// The name of the class this chunk declares, if it declares one.
+    pub fn class_name(&self) -> Option<&str> {
+        self.attrs().find_map(|attr| match attr {
+            Attribute::Class { ty, .. } => Some(ty.as_str()),
+            _ => None,
+        })
+    }
+}
+
+/// A function declared as a method, either `function Foo:bar()` or
+/// `function Foo.bar(self)`.
+struct Method {
+    table: String,
+    name: String,
+    /// The parameters not including `self`
+    params: Vec<String>,
+}
+
+impl Chunk<'_> {
+    /// The `@within` section this chunk belongs to, if any.
+    pub fn section(&self) -> Option<&str> {
+        self.attrs().find_map(|attr| match attr {
+            Attribute::Within { section } => Some(section.as_str()),
+            _ => None,
+        })
+    }
+
+    /// The parameters from a `---@type fun(...)`, if there is one.
+    pub fn fun_type(&self) -> Option<&[(String, String)]> {
+        self.attrs().find_map(|attr| match attr {
+            Attribute::FunType { params, .. } => Some(params.as_slice()),
+            _ => None,
+        })
+    }
+
+    /// `ty` with a `self` return type replaced by the class or module this chunk belongs
+    /// to, so chained calls link back to it. Stays `self` if there isn't one.
+    ///
+    /// `class` is the class the chunk is grouped under. Both `self` and that class's name
+    /// are written as its module path, so they link to it with `--root-module` too.
+    pub fn return_type(&self, ty: &str, class: Option<&str>) -> String {
+        let ty = ty.trim();
+        match class.or(self.class_name()) {
+            Some(class) if ty == "self" || ty == class => return module_path(class),
+            _ if ty != "self" => return ty.to_string(),
+            _ => (),
+        }
+        let group = match &self.decl {
+            // A plain `function foo()` isn't on a table
+            Declaration::Function(_, node)
+                if node
+                    .child_by_field_name("name")
+                    .is_some_and(|name| name.kind() == "identifier") =>
+            {
+                None
+            }
+            decl => decl.group(),
+        };
+        group.unwrap_or(ty).to_string()
+    }
+
+    /// What a metamethod like `M.__add` is for, like `` `+` ``.
+    pub fn metamethod(&self, source: &[u8]) -> Option<&'static str> {
+        let name = self.decl.member_name(source)?;
+        let op = match name.strip_prefix("__")? {
+            "add" => "`+`",
+            "sub" => "`-`",
+            "mul" => "`*`",
+            "div" => "`/`",
+            "mod" => "`%`",
+            "pow" => "`^`",
+            "unm" => "unary `-`",
+            "idiv" => "`//`",
+            "band" => "`&`",
+            "bor" => "`|`",
+            "bxor" => "`~`",
+            "shl" => "`<<`",
+            "shr" => "`>>`",
+            "bnot" => "unary `~`",
+            "concat" => "`..`",
+            "len" => "`#`",
+            "eq" => "`==`",
+            "lt" => "`<`",
+            "le" => "`<=`",
+            "index" => "indexing (`t[k]`)",
+            "newindex" => "assigning to an index (`t[k] = v`)",
+            "call" => "calling (`t(...)`)",
+            "tostring" => "`tostring`",
+            "pairs" => "`pairs`",
+            "close" => "to-be-closed variables",
+            "gc" => "garbage collection",
+            _ => return None,
+        };
+        Some(op)
+    }
+
+    fn method(&self, source: &[u8]) -> Option<Method> {
+        let Declaration::Function(Some(table), decl) = &self.decl else {
+            return None;
+        };
+        let name = decl.child_by_field_name("name")?;
+        let mut params = function_params(*decl, source);
+
+        let method = match name.kind() {
+            "method_index_expression" => name.child_by_field_name("method")?,
+            "dot_index_expression" if params.first().is_some_and(|param| param == "self") => {
+                params.remove(0);
+                name.child_by_field_name("field")?
+            }
+            _ => return None,
+        };
+
+        Some(Method {
+            table: table.clone(),
+            name: method.utf8_text(source).ok()?.to_string(),
+            params,
+        })
+    }
+}
+
+/// Where an attribute goes in LDoc's preferred tag order.
+fn ldoc_order(attr: &Attribute) -> u8 {
+    match attr {
+        Attribute::Class { .. } | Attribute::ClassMod { .. } => 0,
+        Attribute::Param { .. } | Attribute::Field { .. } => 1,
+        Attribute::Return { .. } => 2,
+        Attribute::Raise { .. } => 3,
+        Attribute::See { .. } => 4,
+        _ => 5,
+    }
+}
+
+/// The byte range of a function's body, from after its parameters up to and including
+/// its `end`. Replacing this with ` end` leaves just the signature.
+fn body_range(function: Node) -> Option<std::ops::Range<usize>> {
+    let params = function.child_by_field_name("parameters")?;
+    Some(params.end_byte()..function.end_byte())
+}
+
+/// `line` wrapped at spaces for `--wrap`, so that no line is longer than `width` unless a
+/// single word is. The first `keep` words, like a tag and its type, stay on the first line,
+/// and code spans aren't broken up. LDoc joins the continuation lines back into the same
+/// description.
+fn wrap_line(line: &str, width: usize, keep: usize) -> Vec<String> {
+    let Some(text) = line.strip_prefix("---") else {
+        return vec![line.to_string()];
+    };
+    if line.contains('\n') || line.chars().count() <= width {
+        return vec![line.to_string()];
+    }
+    let trimmed = text.trim_start();
+    let indent = &text[..text.len() - trimmed.len()];
+
+    let mut words = Vec::new();
+    let mut in_code = false;
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (i, c) in trimmed.char_indices() {
+        // Types like `table<string, Foo>` have spaces too, but descriptions may have
+        // unbalanced brackets like `x < y`
+        let in_type = words.len() < keep;
+        match c {
+            '`' => in_code = !in_code,
+            '(' | '[' | '{' | '<' if in_type && !in_code => depth += 1,
+            ')' | ']' | '}' | '>' if in_type && !in_code => depth = depth.saturating_sub(1),
+            ' ' if !in_code && (depth == 0 || !in_type) => {
+                if i > start {
+                    words.push(&trimmed[start..i]);
+                }
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    if start < trimmed.len() {
+        words.push(&trimmed[start..]);
+    }
+    if words.is_empty() {
+        return vec![line.to_string()];
+    }
+
+    let keep = keep.clamp(1, words.len());
+    let continuation = if trimmed.starts_with('@') {
+        "--- ".to_string()
+    } else {
+        format!("---{indent}")
+    };
+    let mut lines = Vec::new();
+    let mut current = format!("---{indent}{}", words[..keep].join(" "));
+    for word in words[keep..].iter() {
+        if current.chars().count() + 1 + word.chars().count() > width {
+            lines.push(current);
+            current = format!("{continuation}{word}");
+        } else {
+            current.push(' ');
+            current.push_str(word);
+        }
+    }
+    lines.push(current);
+    lines
+}
+
+/// Whether `line` is an empty `---` doc comment line.
+fn is_blank_doc_line(line: &str) -> bool {
+    line.trim().trim_start_matches('-').is_empty()
+}
+
+/// The index of the first body line after the summary. Like LuaLS, the summary is the
+/// first paragraph, up to a blank line or a line ending a sentence, so a summary that
+/// wraps onto more lines stays whole. A code fence also ends it.
+fn summary_end(body: &[String]) -> usize {
+    let Some(start) = body.iter().position(|line| !is_blank_doc_line(line)) else {
+        return body.len();
+    };
+    for (i, line) in body.iter().enumerate().skip(start) {
+        let text = line.trim_start_matches('-').trim();
+        if text.starts_with("```") {
+            // There's no summary to split off if it starts with code
+            return if i > start { i } else { body.len() };
+        }
+        if is_blank_doc_line(line) {
+            return i;
+        }
+        if text.ends_with(['.', '?', '!']) {
+            return i + 1;
+        }
+    }
+    body.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<String> {
+        text.lines().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn summary_is_the_first_paragraph() {
+        let body = lines("--- Does a thing\n--- across two lines.\n--- More detail.");
+        assert_eq!(summary_end(&body), 2);
+
+        let body = lines("--- No period\n--- or here\n---\n--- Description.");
+        assert_eq!(summary_end(&body), 2);
+
+        let body = lines("---\n--- Summary.\n--- Description.");
+        assert_eq!(summary_end(&body), 2);
+    }
+
+    #[test]
+    fn summary_ends_at_a_fence() {
+        let body = lines("--- Summary\n---```lua\n---print(1).\n---```");
+        assert_eq!(summary_end(&body), 1);
+
+        let body = lines("---```lua\n---print(1)\n---```");
+        assert_eq!(summary_end(&body), body.len());
+    }
 }
@@ -0,0 +1,246 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Options from `.ldoc_gen.toml`, or an `[ldoc_gen]` table in another project file.
+
+use std::{collections::HashMap, path::Path, sync::OnceLock};
+
+use anyhow::Context;
+
+const CONFIG_FILE: &str = ".ldoc_gen.toml";
+
+/// Project files that can have the config in a `[tool.ldoc_gen]` or `[ldoc_gen]` table
+/// instead of a separate file.
+const PROJECT_FILES: &[&str] = &["pyproject.toml", "Cargo.toml"];
+
+#[derive(Debug)]
+pub struct Config {
+    /// Type names to replace in the output, from `[type_aliases]`
+    pub type_aliases: HashMap<String, String>,
+    /// Path globs and the module prefix for files matching them, from `[module_prefixes]`
+    pub module_prefixes: Vec<(Glob, String)>,
+}
+
+/// A path glob like `plugins/*/lua/**`. `*` matches within a directory, `**` matches any
+/// number of them, and `?` matches one character.
+#[derive(Debug)]
+pub struct Glob {
+    regex: regex::Regex,
+    /// How many characters aren't wildcards, so more specific globs can win
+    literal_len: usize,
+    /// The directories before the first wildcard, like `plugins/` for `plugins/*/lua/**`
+    base: String,
+}
+
+impl Glob {
+    fn new(glob: &str) -> anyhow::Result<Self> {
+        let mut pattern = String::from("^");
+        let mut literal_len = 0;
+        let mut chars = glob.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '*' if chars.peek() == Some(&'*') => {
+                    chars.next();
+                    // `a/**/b` matches `a/b` too
+                    if chars.peek() == Some(&'/') {
+                        chars.next();
+                        pattern.push_str("(.*/)?");
+                    } else {
+                        pattern.push_str(".*");
+                    }
+                }
+                '*' => pattern.push_str("[^/]*"),
+                '?' => pattern.push_str("[^/]"),
+                c => {
+                    literal_len += 1;
+                    pattern.push_str(&regex::escape(&c.to_string()));
+                }
+            }
+        }
+        pattern.push('$');
+        let literal = &glob[..glob.find(['*', '?']).unwrap_or(glob.len())];
+        let base = match literal.rfind('/') {
+            Some(i) => literal[..=i].to_string(),
+            None => String::new(),
+        };
+        Ok(Self {
+            regex: regex::Regex::new(&pattern)?,
+            literal_len,
+            base,
+        })
+    }
+}
+
+/// Renames that happen even without a config. `[type_aliases]` entries for the same names
+/// win, so `unknown = "unknown"` turns one off.
+const DEFAULT_TYPE_ALIASES: &[(&str, &str)] = &[
+    // `unknown` isn't an LDoc type
+    ("unknown", "any"),
+];
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            type_aliases: DEFAULT_TYPE_ALIASES
+                .iter()
+                .map(|(from, to)| (from.to_string(), to.to_string()))
+                .collect(),
+            module_prefixes: Vec::new(),
+        }
+    }
+}
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// What `integer` is written as with `--integer-as`, over any `[type_aliases]` entry
+static INTEGER_AS: OnceLock<&'static str> = OnceLock::new();
+
+/// Write `integer` as `name`. This should be called once at startup.
+pub fn set_integer_as(name: &'static str) {
+    let _ = INTEGER_AS.set(name);
+}
+
+/// Load the config from `path`. This should be called once at startup.
+pub fn init(path: &Path) -> anyhow::Result<()> {
+    let table = read_table(path)?;
+    let _ = CONFIG.set(parse(table, &path.display().to_string())?);
+    Ok(())
+}
+
+/// Load the config from the closest directory at or above `dir` that has one, either
+/// `.ldoc_gen.toml` or a table in one of the [`PROJECT_FILES`]. A `.ldoc_gen.toml` wins over
+/// a project file in the same directory. This should be called once at startup, and does
+/// nothing if there isn't a config anywhere.
+pub fn discover(dir: &Path) -> anyhow::Result<()> {
+    // The parent of a file in the current directory is empty
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+    for dir in dir.ancestors() {
+        let path = dir.join(CONFIG_FILE);
+        if path.is_file() {
+            return init(&path);
+        }
+
+        for file in PROJECT_FILES {
+            // Not ours, so one that can't be read just doesn't count
+            let path = dir.join(file);
+            let Ok(mut table) = read_table(&path) else {
+                continue;
+            };
+            let (name, table) = match table.remove("tool").and_then(|tool| match tool {
+                toml::Value::Table(mut tool) => tool.remove("ldoc_gen"),
+                _ => None,
+            }) {
+                Some(table) => ("tool.ldoc_gen", table),
+                None => match table.remove("ldoc_gen") {
+                    Some(table) => ("ldoc_gen", table),
+                    None => continue,
+                },
+            };
+            let toml::Value::Table(table) = table else {
+                anyhow::bail!("{}: [{name}] must be a table", path.display());
+            };
+            let _ = CONFIG.set(parse(table, &format!("{} [{name}]", path.display()))?);
+            return Ok(());
+        }
+    }
+    Ok(())
+}
+
+fn read_table(path: &Path) -> anyhow::Result<toml::Table> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("couldn't read {}", path.display()))?;
+    toml::from_str::<toml::Table>(&contents)
+        .with_context(|| format!("couldn't parse {}", path.display()))
+}
+
+/// The config from `table`. `source` is where it came from, for errors.
+fn parse(table: toml::Table, source: &str) -> anyhow::Result<Config> {
+    let mut config = Config::default();
+    for (key, value) in table {
+        match (key.as_str(), value) {
+            ("type_aliases", toml::Value::Table(aliases)) => {
+                for (from, to) in aliases {
+                    let toml::Value::String(to) = to else {
+                        anyhow::bail!("{source}: type_aliases.{from} must be a string");
+                    };
+                    config.type_aliases.insert(from, to);
+                }
+            }
+            ("module_prefixes", toml::Value::Table(prefixes)) => {
+                for (glob, prefix) in prefixes {
+                    let toml::Value::String(prefix) = prefix else {
+                        anyhow::bail!("{source}: module_prefixes.\"{glob}\" must be a string");
+                    };
+                    let compiled = Glob::new(&glob)
+                        .with_context(|| format!("{source}: invalid glob \"{glob}\""))?;
+                    config.module_prefixes.push((compiled, prefix));
+                }
+            }
+            (key, _) => anyhow::bail!("{source}: unknown option `{key}`"),
+        }
+    }
+    Ok(config)
+}
+
+fn get() -> &'static Config {
+    CONFIG.get_or_init(Config::default)
+}
+
+/// The `[module_prefixes]` prefix for a file at `path`, relative to its `--path` with `/`
+/// between components, and the directories before the glob's first wildcard. The glob with
+/// the most non-wildcard characters wins.
+pub fn module_prefix(path: &str) -> Option<(&'static str, &'static str)> {
+    get()
+        .module_prefixes
+        .iter()
+        .filter(|(glob, _)| glob.regex.is_match(path))
+        .max_by_key(|(glob, _)| glob.literal_len)
+        .map(|(glob, prefix)| (prefix.as_str(), glob.base.as_str()))
+}
+
+/// Replace whole type names in `ty` using `[type_aliases]` and `--integer-as`. Names
+/// inside string literals are left alone.
+pub fn rename_types(ty: &str) -> String {
+    let aliases = &get().type_aliases;
+    let integer = INTEGER_AS.get();
+    if aliases.is_empty() && integer.is_none() {
+        return ty.to_string();
+    }
+
+    let mut ret = String::new();
+    let mut name = String::new();
+    let mut in_string = None;
+    let flush = |name: &mut String, ret: &mut String| {
+        match (name.as_str(), integer) {
+            ("integer", Some(integer)) => ret.push_str(integer),
+            (name, _) => match aliases.get(name) {
+                Some(alias) => ret.push_str(alias),
+                None => ret.push_str(name),
+            },
+        }
+        name.clear();
+    };
+
+    for c in ty.chars() {
+        match in_string {
+            None if c.is_alphanumeric() || c == '_' || c == '.' => {
+                name.push(c);
+                continue;
+            }
+            None if c == '"' || c == '\'' => in_string = Some(c),
+            Some(quote) if c == quote => in_string = None,
+            _ => (),
+        }
+        flush(&mut name, &mut ret);
+        ret.push(c);
+    }
+    flush(&mut name, &mut ret);
+
+    ret
+}
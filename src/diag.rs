@@ -0,0 +1,160 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Reporting of errors and warnings about the files being converted.
+
+use std::{
+    io::Write,
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        OnceLock,
+    },
+};
+
+use clap::ValueEnum;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum ErrorFormat {
+    /// Human-readable lines
+    #[default]
+    Text,
+    /// One JSON object per line with `file`, `line`, `kind`, and `message`
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum ErrorStream {
+    #[default]
+    Stderr,
+    Stdout,
+}
+
+/// What went wrong.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    /// The file couldn't be used as input
+    Input,
+    /// tree-sitter couldn't parse the file at all
+    Parse,
+    /// The file has a syntax error
+    Syntax,
+    /// A `@see` reference couldn't be resolved
+    UnresolvedSee,
+    /// A function is missing `@param` or `@return` docs
+    MissingDoc,
+    /// An `@alias` couldn't be parsed
+    Alias,
+    /// A `@param` doesn't match the function's parameters
+    ParamMismatch,
+    /// The same `@param` is documented more than once
+    DuplicateParam,
+    /// A `return` returns a different number of values than there are `@return`s
+    ReturnMismatch,
+    /// A `@class` inherits from a class that isn't declared anywhere
+    UnknownParent,
+    /// A type in a `@param`, `@return`, or `@field` isn't a class from the run, with
+    /// `--check-types`
+    UnknownType,
+    /// A `--#region` without an `--#endregion`, with `--regions-as-sections`
+    UnclosedRegion,
+    /// A `@return` without a type that there's no typed `@return` before to describe
+    TypelessReturn,
+    /// The `--post-process` command failed on an output file
+    PostProcess,
+}
+
+impl Kind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Kind::Input => "input",
+            Kind::Parse => "parse",
+            Kind::Syntax => "syntax",
+            Kind::UnresolvedSee => "unresolved-see",
+            Kind::MissingDoc => "missing-doc",
+            Kind::Alias => "alias",
+            Kind::ParamMismatch => "param-mismatch",
+            Kind::DuplicateParam => "duplicate-param",
+            Kind::ReturnMismatch => "return-mismatch",
+            Kind::UnknownParent => "unknown-parent",
+            Kind::UnknownType => "unknown-type",
+            Kind::UnclosedRegion => "unclosed-region",
+            Kind::TypelessReturn => "typeless-return",
+            Kind::PostProcess => "post-process",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Config {
+    format: ErrorFormat,
+    stream: ErrorStream,
+    /// Only errors are printed, warnings are just counted
+    quiet: bool,
+}
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+static WARNINGS: AtomicUsize = AtomicUsize::new(0);
+
+/// Set how diagnostics are reported. This should be called once at startup.
+pub fn init(format: ErrorFormat, stream: ErrorStream, quiet: bool) {
+    let _ = CONFIG.set(Config {
+        format,
+        stream,
+        quiet,
+    });
+}
+
+/// Report an error in `file`. `line` is 1-based.
+pub fn error(file: &Path, line: Option<usize>, kind: Kind, message: &str) {
+    report(file, line, kind, message, false);
+}
+
+/// Report a warning in `file`. `line` is 1-based. It still counts towards
+/// [`warning_count`] with `--quiet`, it just isn't printed.
+pub fn warn(file: &Path, line: Option<usize>, kind: Kind, message: &str) {
+    WARNINGS.fetch_add(1, Ordering::Relaxed);
+    report(file, line, kind, message, true);
+}
+
+/// Start counting warnings from zero again, for a new `--watch` run.
+pub fn reset_warnings() {
+    WARNINGS.store(0, Ordering::Relaxed);
+}
+
+/// How many warnings have been reported so far.
+pub fn warning_count() -> usize {
+    WARNINGS.load(Ordering::Relaxed)
+}
+
+fn report(file: &Path, line: Option<usize>, kind: Kind, message: &str, is_warning: bool) {
+    let config = CONFIG.get_or_init(Config::default);
+    if is_warning && config.quiet {
+        return;
+    }
+
+    let out = match config.format {
+        ErrorFormat::Text => {
+            let location = match line {
+                Some(line) => format!("{}:{line}", file.display()),
+                None => file.display().to_string(),
+            };
+            let prefix = if is_warning { "Warning: " } else { "" };
+            format!("{prefix}{location}: {message}")
+        }
+        ErrorFormat::Json => serde_json::json!({
+            "file": file.display().to_string(),
+            "line": line,
+            "kind": kind.as_str(),
+            "message": message,
+        })
+        .to_string(),
+    };
+
+    let _ = match config.stream {
+        ErrorStream::Stderr => writeln!(std::io::stderr().lock(), "{out}"),
+        ErrorStream::Stdout => writeln!(std::io::stdout().lock(), "{out}"),
+    };
+}
@@ -0,0 +1,127 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Listing the fields a class inherits for `--show-inherited`, since LDoc doesn't know
+//! about inheritance.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    attr::{module_path, Attribute, Spanned},
+    chunk::Chunk,
+    diag,
+    strings::{self, Text},
+    SourceFile,
+};
+
+/// A class's parents and own `@field`s, from all of its declarations.
+#[derive(Debug, Default)]
+struct Class {
+    parents: Vec<String>,
+    fields: Vec<Attribute>,
+}
+
+/// Add the `@field`s each class inherits to its first declaration, with a note saying
+/// which class they're from. Fields the class redefines aren't added.
+///
+/// Parents that aren't declared anywhere in the run are warned about once and skipped.
+pub fn add_inherited_fields(parsed: &mut [(&SourceFile, Vec<Chunk>)]) {
+    let mut classes = HashMap::<String, Class>::new();
+    for (_, chunks) in parsed.iter() {
+        for chunk in chunks.iter() {
+            for attr in chunk.attrs() {
+                if let Attribute::Class { ty, parents, .. } = attr {
+                    let class = classes.entry(ty.clone()).or_default();
+                    class.parents.extend(parents.iter().cloned());
+                    class.fields.extend(
+                        chunk
+                            .attrs()
+                            .filter(|attr| matches!(attr, Attribute::Field { .. }))
+                            .cloned(),
+                    );
+                }
+            }
+        }
+    }
+
+    let mut done = HashSet::<String>::new();
+    let mut unknown = HashSet::<String>::new();
+    for (file, chunks) in parsed.iter_mut() {
+        for chunk in chunks.iter_mut() {
+            let Some(name) = chunk.class_name().map(str::to_string) else {
+                continue;
+            };
+            if !done.insert(name.clone()) {
+                continue;
+            }
+            let line = chunk
+                .attributes
+                .iter()
+                .find(|attr| matches!(attr.value, Attribute::Class { .. }))
+                .and_then(|attr| attr.line);
+
+            let mut names = classes[&name]
+                .fields
+                .iter()
+                .filter_map(field_name)
+                .map(str::to_string)
+                .collect::<HashSet<_>>();
+            let mut inherited = Vec::new();
+            let mut visited = HashSet::from([name.clone()]);
+            let mut queue = classes[&name].parents.clone();
+            while !queue.is_empty() {
+                let parent = queue.remove(0);
+                if !visited.insert(parent.clone()) {
+                    continue;
+                }
+                let Some(class) = classes.get(&parent) else {
+                    if !unknown.insert(parent.clone()) {
+                        continue;
+                    }
+                    diag::warn(
+                        &file.path,
+                        line,
+                        diag::Kind::UnknownParent,
+                        &format!("class {name} inherits from unknown class `{parent}`"),
+                    );
+                    continue;
+                };
+                for field in class.fields.iter() {
+                    if field_name(field).is_some_and(|field| names.insert(field.to_string())) {
+                        inherited.push(with_note(field, &parent));
+                    }
+                }
+                queue.extend(class.parents.iter().cloned());
+            }
+
+            chunk
+                .attributes
+                .extend(inherited.into_iter().map(Spanned::from));
+        }
+    }
+}
+
+fn field_name(attr: &Attribute) -> Option<&str> {
+    match attr {
+        Attribute::Field { name, .. } => Some(name),
+        _ => None,
+    }
+}
+
+/// `field` with a note that it's inherited from `parent` in its description.
+fn with_note(field: &Attribute, parent: &str) -> Attribute {
+    let mut field = field.clone();
+    if let Attribute::Field { desc, .. } = &mut field {
+        let note = format!(
+            "{} `{}`.",
+            strings::get(Text::InheritedFrom),
+            module_path(parent)
+        );
+        *desc = Some(match desc.take() {
+            Some(desc) => format!("{desc} ({note})"),
+            None => note,
+        });
+    }
+    field
+}
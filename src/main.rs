@@ -2,291 +2,2759 @@
 // License, v. 2.0. If a copy of the MPL was not distributed with this
 // file, You can obtain one at https://mozilla.org/MPL/2.0/.
 
-#![feature(lazy_cell)]
+mod attr;
+mod check;
+mod chunk;
+mod config;
+mod diag;
+mod inherit;
+mod markdown;
+mod progress;
+mod regions;
+mod render;
+mod stats;
+mod strings;
+mod symbols;
+#[cfg(not(feature = "pcre2"))]
+mod type_parser;
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::LazyLock,
+    time::{Duration, SystemTime},
+};
+
+use anyhow::Context;
+use attr::{attributes_from_comment_block, AliasMember, Attribute, Spanned, Visibility};
+use chunk::Chunk;
+use clap::Parser;
+use diag::{ErrorFormat, ErrorStream};
+use progress::Progress;
+use rayon::prelude::*;
+use render::Renderer;
+use stats::Phase;
+use symbols::Symbols;
+use tree_sitter::{InputEdit, Node, Point, Tree, TreeCursor};
+use walkdir::WalkDir;
+
+const OUTPUT_DIR: &str = ".ldoc_gen";
+const GENERATED_NOTICE: &str = "Generated by ldoc_gen. DO NOT EDIT.";
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+
+    // clap can only conflict with the flag, not one of its values
+    if args.output_on_stdout && args.error_stream == ErrorStream::Stdout {
+        anyhow::bail!("--output-on-stdout can't be used with --error-stream stdout");
+    }
+    diag::init(args.error_format, args.error_stream, args.quiet);
+    if args.stats {
+        stats::enable();
+    }
+    if !args.doc_prefix.starts_with("--") {
+        anyhow::bail!(
+            "--doc-prefix has to start a Lua comment, got `{}`",
+            args.doc_prefix
+        );
+    }
+    crate::attr::set_doc_prefix(args.doc_prefix.clone());
+    if args.trace_regex {
+        crate::attr::enable_trace_regex();
+    }
+    if let Some(prefix) = &args.root_module {
+        crate::attr::set_root_module(prefix.clone());
+    }
+    if let Some(integer) = args.integer_as {
+        config::set_integer_as(match integer {
+            IntegerAs::Int => "int",
+            IntegerAs::Number => "number",
+            IntegerAs::Integer => "integer",
+        });
+    }
+    strings::init(args.lang.as_deref())?;
+    match &args.config {
+        Some(path) => config::init(path)?,
+        None => config::discover(args.source_root(0))?,
+    }
+
+    let root_dirs = (0..args.path.len())
+        .map(|i| args.root_dir(i))
+        .collect::<Vec<_>>();
+    for (i, dir) in root_dirs.iter().enumerate() {
+        if let Some(j) = root_dirs[..i].iter().position(|other| other == dir) {
+            anyhow::bail!(
+                "--path {} and --path {} would both be written to {}",
+                args.path[j].display(),
+                args.path[i].display(),
+                dir.display()
+            );
+        }
+    }
+
+    let mut trees = Trees::new();
+    if args.watch {
+        return watch(&args, &mut trees);
+    }
+    if !run(&args, &mut trees)? {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Each file's preprocessed source and the tree parsed from it in the previous run, for
+/// `--watch` to reparse changed files incrementally.
+type Trees = HashMap<PathBuf, (String, Tree)>;
+
+/// How long `--watch` waits between checks for changed files.
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Convert every file, returning whether the run should exit successfully. `trees` are
+/// the trees from the previous run, and are replaced with this run's with `--watch`.
+fn run(args: &Args, trees: &mut Trees) -> anyhow::Result<bool> {
+    let out_dir = args.out_dir.join(OUTPUT_DIR);
+
+    if !args.check && !args.list_files && !args.output_on_stdout {
+        std::fs::create_dir_all(&out_dir)?;
+    }
+
+    let paths = stats::time(Phase::Walk, || match &args.files_from {
+        Some(manifest) => read_manifest(manifest, args),
+        None => Ok(walk_paths(args, &out_dir)),
+    })?;
+
+    if args.list_files {
+        let mut stdout = std::io::stdout().lock();
+        for path in paths.iter() {
+            let line = if args.verbose {
+                let out_path = match &args.single_file {
+                    Some(name) => out_dir.join(name),
+                    None => out_dir.join(args.output_path(path)),
+                };
+                format!("{} -> {}", path.display(), out_path.display())
+            } else {
+                path.display().to_string()
+            };
+            // Stop quietly when the output is closed, like when piped into `head`
+            if writeln!(stdout, "{line}").is_err() {
+                break;
+            }
+        }
+        return Ok(true);
+    }
+
+    let progress = Progress::new(paths.len(), !(args.no_progress || args.quiet));
+
+    // Reading and parsing is done in parallel with a parser per worker thread
+    let files = paths
+        .into_par_iter()
+        .map_init(
+            || {
+                let mut parser = tree_sitter::Parser::new();
+                parser
+                    .set_language(tree_sitter_lua::language())
+                    .expect("tree-sitter-lua is incompatible with tree-sitter");
+                parser
+            },
+            |parser, path| {
+                let previous = trees.get(&path);
+                let file = read_source_file(parser, path, args, previous);
+                progress.inc();
+                file
+            },
+        )
+        .collect::<anyhow::Result<Vec<_>>>()?
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+
+    progress.finish();
+
+    let mut parsed = Vec::<(&SourceFile, Vec<Chunk>)>::new();
+
+    // Aliases are global in LuaLS, so collect them from every file
+    let mut aliases = files
+        .iter()
+        .flat_map(|file| file.aliases.iter())
+        .map(|(name, members)| (name.clone(), members.clone()))
+        .collect::<HashMap<_, _>>();
+    for cycle in crate::attr::resolve_aliases(&mut aliases) {
+        let Some(file) = files
+            .iter()
+            .find(|file| file.aliases.contains_key(&cycle[0]))
+        else {
+            continue;
+        };
+        diag::warn(
+            &file.path,
+            None,
+            diag::Kind::Alias,
+            &format!("@alias refers to itself: {}", cycle.join(" -> ")),
+        );
+    }
+
+    for file in files.iter() {
+        let mut chunks = stats::time(Phase::Regex, || {
+            build_chunks(file.tree.root_node(), file.contents.as_bytes())
+        })?;
+
+        warn_typeless_returns(file, &mut chunks);
+
+        if args.regions_as_sections {
+            regions::add_region_sections(file, &mut chunks);
+        }
+
+        // Document the fields of a class's table constructor
+        for chunk in chunks.iter_mut().filter(|chunk| chunk.is_class()) {
+            let Declaration::Variable(_, node) = &chunk.decl else {
+                continue;
+            };
+            for field in table_fields(*node, file.contents.as_bytes()) {
+                let Attribute::Field { name, .. } = &field else {
+                    continue;
+                };
+                // Explicit @fields take priority
+                let documented = chunk.attrs().any(
+                    |attr| matches!(attr, Attribute::Field { name: documented, .. } if documented == name),
+                );
+                if !documented {
+                    chunk.attributes.push(field.into());
+                }
+            }
+        }
+
+        if args.descend_bodies {
+            let bodies = function_body_chunks(file.tree.root_node(), file.contents.as_bytes())?;
+            let fields = collect_self_fields(&bodies);
+            for chunk in chunks.iter_mut() {
+                let Declaration::Variable(name, _) = &chunk.decl else {
+                    continue;
+                };
+                if !chunk.is_class() {
+                    continue;
+                }
+                if let Some(fields) = fields.get(name) {
+                    chunk
+                        .attributes
+                        .extend(fields.iter().cloned().map(Spanned::from));
+                }
+            }
+
+            // Classes declared inside functions, like the shape a factory returns, get
+            // their own page
+            chunks.extend(
+                bodies
+                    .into_iter()
+                    .flat_map(|(_, chunks)| chunks)
+                    .filter(Chunk::is_class),
+            );
+        }
+
+        stats::time(Phase::Regex, || {
+            for chunk in chunks.iter_mut() {
+                crate::attr::expand_aliases(&mut chunk.attributes, &aliases);
+            }
+        });
+
+        mark_classmod_modules(file, &mut chunks);
+        attach_return_docs(file, &mut chunks);
+
+        parsed.push((file, chunks));
+    }
+
+    crate::attr::set_module_prefixes(module_prefixes(&parsed, args));
+
+    // Now that every file is parsed we know all the modules and classes, so cross-references
+    // can be resolved.
+    let symbols = Symbols::collect(
+        parsed
+            .iter()
+            .map(|(file, chunks)| (*file, chunks.as_slice())),
+    );
+
+    for (file, chunks) in parsed.iter_mut() {
+        symbols.resolve_see(&file.path, chunks);
+        symbols.link_deprecated(&file.path, chunks);
+        symbols.link_requires(file, chunks);
+        symbols.link_types(&file.path, chunks, args.check_types);
+        // Markdown has no way to link to them
+        if args.link_inline_refs && args.format == OutputFormat::Ldoc {
+            symbols.link_inline_refs(&file.path, chunks);
+        }
+    }
+
+    if args.show_inherited {
+        inherit::add_inherited_fields(&mut parsed);
+    }
+
+    let keep_duplicates = args.duplicate_params == DuplicateParams::Keep;
+    let duplicates = parsed
+        .iter_mut()
+        .map(|(file, chunks)| {
+            check::check_duplicate_params(file, chunks, keep_duplicates, args.strict)
+        })
+        .sum::<usize>();
+
+    // --check already reports undocumented params
+    let mismatches = if args.no_param_check {
+        0
+    } else {
+        parsed
+            .iter()
+            .map(|(file, chunks)| check::check_param_names(file, chunks, !args.check, args.strict))
+            .sum::<usize>()
+    } + duplicates;
+
+    if args.check_returns {
+        for (file, chunks) in parsed.iter() {
+            check::check_return_counts(file, chunks);
+        }
+    }
+
+    if args.check {
+        let gaps = parsed
+            .iter()
+            .map(|(file, chunks)| check::check_coverage(file, chunks))
+            .sum::<usize>();
+        if gaps > 0 {
+            return Ok(false);
+        }
+        if args.stats {
+            stats::print();
+        }
+        return Ok(check_warnings(args, mismatches));
+    }
+
+    if args.report {
+        print_report(&parsed, args);
+    }
+
+    let renderer = render::renderer(args);
+
+    // Rendered members of classes from other files, by class name, for each file
+    let mut merged = if args.merge_modules {
+        stats::time(Phase::Render, || {
+            merge_modules(&mut parsed, renderer.as_ref(), args)
+        })
+    } else {
+        Vec::new()
+    };
+
+    if args.fields_at_class {
+        for (_, chunks) in parsed.iter_mut() {
+            gather_class_fields(chunks);
+        }
+    }
+
+    if let Some(dir) = &args.examples_dir {
+        for (_, chunks) in parsed.iter_mut() {
+            add_examples(dir, chunks)?;
+        }
+    }
+
+    let example_regex = crate::attr::example_regex(&args.example_headings);
+    let header = args.header()?;
+
+    // (source path, ldoc_text) for --single-file and --output-on-stdout
+    let mut sections = Vec::<(PathBuf, String)>::new();
+    // Everything written this run, for --clean
+    let mut written = HashSet::<PathBuf>::new();
+    // (module name, output path relative to `out_dir`) for --emit-index
+    let mut modules = Vec::<(String, PathBuf)>::new();
+    // What each source file turned into, for --manifest
+    let mut manifest = Vec::<ManifestEntry>::new();
+    // Output files --post-process failed on
+    let mut post_process_failures = 0;
+    let mut post_process = |path: &Path, text: String| match &args.post_process {
+        Some(command) => match run_post_process(command, path, text.clone()) {
+            Ok(text) => text,
+            Err(err) => {
+                post_process_failures += 1;
+                diag::error(path, None, diag::Kind::PostProcess, &format!("{err:#}"));
+                text
+            }
+        },
+        None => text,
+    };
+
+    for (i, (file, chunks)) in parsed.iter().enumerate() {
+        let source = file.contents.as_bytes();
+        let module = returned_name(file.tree.root_node(), source);
+        let merged = merged.get_mut(i).map(std::mem::take).unwrap_or_default();
+        let first_module = modules.len();
+        let mut ldoc_text = stats::time(Phase::Render, || {
+            render_chunks(
+                chunks,
+                source,
+                module,
+                None,
+                merged,
+                renderer.as_ref(),
+                args,
+            )
+        });
+
+        if args.infer_module
+            && !chunks.iter().any(Chunk::is_class)
+            && returns_table(file.tree.root_node())
+        {
+            let name = args.module_name(&file.path);
+            let module = crate::attr::module_path(&name);
+            modules.push((module.clone(), args.output_path(&file.path)));
+            let usage = args
+                .examples_dir
+                .as_deref()
+                .map(|dir| example_usage(dir, &name))
+                .transpose()?
+                .flatten()
+                .map(|usage| renderer.render_attribute(&usage));
+            let mut header = match (args.format, usage) {
+                (OutputFormat::Ldoc, None) => format!("\n---@module {module}\n"),
+                (OutputFormat::Ldoc, Some(usage)) => format!("\n---@module {module}\n{usage}\n"),
+                (OutputFormat::Markdown, None) => format!("\n## {module}\n"),
+                (OutputFormat::Markdown, Some(usage)) => {
+                    format!("\n## {module}\n\n{}\n", usage.trim_end())
+                }
+            };
+            // The docs on `return M` describe the module
+            if let Some(doc) = chunks.iter().find(|chunk| documents_return(chunk, source)) {
+                let doc = Chunk {
+                    body: doc.body.clone(),
+                    attributes: doc.attributes.clone(),
+                    decl: Declaration::Dangling,
+                };
+                let doc = renderer.render_chunk(&doc, None, source);
+                match args.format {
+                    OutputFormat::Ldoc => header.insert_str(1, &format!("{}\n", doc.trim())),
+                    OutputFormat::Markdown => header.push_str(&format!("\n{}\n", doc.trim())),
+                }
+            }
+            ldoc_text.insert_str(0, &header);
+        }
+
+        // Markdown can keep its examples and fences as they are
+        if args.format == OutputFormat::Ldoc {
+            stats::time(Phase::Regex, || {
+                if !args.no_usage {
+                    crate::attr::replace_examples(&mut ldoc_text, &example_regex);
+                }
+
+                crate::attr::replace_fences(&mut ldoc_text);
+            });
+        }
+
+        let is_empty = ldoc_text.trim().is_empty() && !args.keep_empty;
+
+        if !is_empty {
+            let classes = chunks
+                .iter()
+                .filter(|chunk| chunk.is_class())
+                .filter_map(Chunk::class_name);
+            for class in classes {
+                modules.push((
+                    crate::attr::module_path(class),
+                    args.output_path(&file.path),
+                ));
+            }
+
+            manifest.push(ManifestEntry {
+                source: args.relative_path(&file.path),
+                output: match &args.single_file {
+                    Some(name) => out_dir.join(name),
+                    None => out_dir.join(args.output_path(&file.path)),
+                },
+                modules: modules[first_module..]
+                    .iter()
+                    .map(|(module, _)| module.clone())
+                    .collect(),
+                attributes: chunks.iter().map(|chunk| chunk.attributes.len()).sum(),
+            });
+        }
+
+        if args.single_file.is_some() || args.output_on_stdout {
+            if !is_empty {
+                sections.push((file.path.clone(), ldoc_text));
+            }
+        } else {
+            let out_path = out_dir.join(args.output_path(&file.path));
+            if is_empty {
+                // Don't leave one behind from a previous run either
+                if out_path.is_file() {
+                    std::fs::remove_file(out_path)?;
+                }
+                continue;
+            }
+            if let Some(parent) = out_path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            let source_comment = if args.emit_source_comment {
+                // A plain comment so LDoc doesn't take it for the module's doc comment
+                let rel_path = slash_path(&args.relative_path(&file.path));
+                let text = strings::get(strings::Text::GeneratedFrom);
+                match args.format {
+                    OutputFormat::Ldoc => format!("-- {text} {rel_path}\n"),
+                    OutputFormat::Markdown => format!("<!-- {text} {rel_path} -->\n"),
+                }
+            } else {
+                String::new()
+            };
+            let text = normalize_whitespace(&format!("{header}{source_comment}\n{ldoc_text}"));
+            let text = post_process(&out_path, text);
+            stats::time(Phase::Write, || std::fs::write(&out_path, text))?;
+            written.insert(out_path);
+        }
+    }
+
+    if let Some(name) = &args.single_file {
+        let text = join_sections(sections, &header, args);
+        let text = post_process(&out_dir.join(name), text);
+        stats::time(Phase::Write, || std::fs::write(out_dir.join(name), text))?;
+        written.insert(out_dir.join(name));
+    } else if args.output_on_stdout {
+        let text = join_sections(sections, &header, args);
+        // Stop quietly when the output is closed, like when piped into `head`
+        let _ = stats::time(Phase::Write, || {
+            std::io::stdout().lock().write_all(text.as_bytes())
+        });
+    }
+
+    if args.emit_index {
+        if let Some(name) = &args.single_file {
+            // Everything is in the one file
+            for (_, path) in modules.iter_mut() {
+                *path = name.clone();
+            }
+        }
+        let index_path = out_dir.join(format!("index.{}", args.out_ext()));
+        if written.contains(&index_path) {
+            anyhow::bail!(
+                "--emit-index would overwrite {}, which was generated from a source file",
+                index_path.display()
+            );
+        }
+        let text = normalize_whitespace(&format!("{header}\n{}", render_index(modules, args)));
+        std::fs::write(&index_path, post_process(&index_path, text))?;
+        written.insert(index_path);
+    }
+
+    if args.clean {
+        clean_output(&out_dir, &written)?;
+    }
+
+    if let Some(path) = &args.manifest {
+        write_manifest(path, manifest)?;
+    }
+
+    if args.stats {
+        stats::print();
+    }
+
+    if args.watch {
+        *trees = files
+            .iter()
+            .map(|file| {
+                (
+                    file.path.clone(),
+                    (file.contents.clone(), file.tree.clone()),
+                )
+            })
+            .collect();
+    }
+
+    if post_process_failures > 0 {
+        eprintln!("Failing because --post-process failed on {post_process_failures} file(s)");
+        return Ok(false);
+    }
+    Ok(check_warnings(args, mismatches))
+}
+
+/// Convert every file, then again whenever one of them changes, for `--watch`. Changed
+/// files are reparsed from their previous tree.
+fn watch(args: &Args, trees: &mut Trees) -> anyhow::Result<()> {
+    let out_dir = args.out_dir.join(OUTPUT_DIR);
+    let mut last = None;
+    loop {
+        let times = modified_times(args, &out_dir)?;
+        if last.as_ref() != Some(&times) {
+            if last.is_some() {
+                eprintln!("Files changed, converting again");
+            }
+            last = Some(times);
+            diag::reset_warnings();
+            // Failures are only reported, so fixing them is picked up by the next run
+            if let Err(err) = run(args, trees) {
+                eprintln!("Error: {err:#}");
+            }
+        }
+        std::thread::sleep(WATCH_INTERVAL);
+    }
+}
+
+/// When each source file was last changed, so `--watch` can tell when to convert again.
+/// Files added or removed count as changes too.
+fn modified_times(
+    args: &Args,
+    out_dir: &Path,
+) -> anyhow::Result<HashMap<PathBuf, Option<SystemTime>>> {
+    let paths = match &args.files_from {
+        Some(manifest) => read_manifest(manifest, args)?,
+        None => walk_paths(args, out_dir),
+    };
+    Ok(paths
+        .into_iter()
+        .map(|path| {
+            let modified = std::fs::metadata(&path).and_then(|meta| meta.modified());
+            (path, modified.ok())
+        })
+        .collect())
+}
+
+/// The generated docs for every file in one text, in path order, each section headed with
+/// the module and source file it's from.
+fn join_sections(mut sections: Vec<(PathBuf, String)>, header: &str, args: &Args) -> String {
+    // Sort by path so the output is the same across runs and platforms
+    sections.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    let mut text = header.to_string();
+    for (path, ldoc_text) in sections {
+        let rel_path = slash_path(&args.relative_path(&path));
+        let module = crate::attr::module_path(&args.module_name(&path));
+        text.push_str(&match args.format {
+            OutputFormat::Ldoc => format!("\n-- Module: {module} ({rel_path})\n"),
+            OutputFormat::Markdown => format!("\n# {module}\n\n`{rel_path}`\n"),
+        });
+        text.push_str(&ldoc_text);
+    }
+    normalize_whitespace(&text)
+}
+
+/// The `[module_prefixes]` prefix of every module and class, from the path of the file
+/// it's declared in. Names declared in more than one file get the first one's.
+fn module_prefixes(parsed: &[(&SourceFile, Vec<Chunk>)], args: &Args) -> HashMap<String, String> {
+    let mut prefixes = HashMap::new();
+    for (file, chunks) in parsed.iter() {
+        let (_, rel_path) = args.path_in_root(&file.path);
+        let Some((prefix, _)) = config::module_prefix(&slash_path(&rel_path)) else {
+            continue;
+        };
+        let classes = chunks
+            .iter()
+            .flat_map(|chunk| chunk.attrs())
+            .filter_map(|attr| match attr {
+                Attribute::Class { ty, .. } => Some(ty.clone()),
+                _ => None,
+            });
+        // The name --infer-module gives it
+        for name in classes.chain([args.module_name(&file.path)]) {
+            prefixes.entry(name).or_insert_with(|| prefix.to_string());
+        }
+    }
+    prefixes
+}
+
+/// The `--emit-index` page listing every module and class, sorted by name.
+fn render_index(mut modules: Vec<(String, PathBuf)>, args: &Args) -> String {
+    modules.sort();
+    modules.dedup_by(|(a, _), (b, _)| a == b);
+
+    let title = strings::get(strings::Text::Index);
+    let mut ret = String::new();
+    match args.format {
+        OutputFormat::Ldoc => {
+            ret.push_str(&format!("---{title}\n---\n---@module index\n"));
+            for (module, _) in modules {
+                ret.push_str(&format!("---@see {module}\n"));
+            }
+        }
+        OutputFormat::Markdown => {
+            ret.push_str(&format!("# {title}\n\n"));
+            for (module, path) in modules {
+                let path = slash_path(&path);
+                ret.push_str(&format!("- [`{module}`]({path})\n"));
+            }
+        }
+    }
+    ret
+}
+
+/// A generated file in the `--manifest`.
+struct ManifestEntry {
+    /// Relative to `--path`
+    source: PathBuf,
+    output: PathBuf,
+    /// The modules and classes emitted from the source
+    modules: Vec<String>,
+    /// How many attributes were converted
+    attributes: usize,
+}
+
+/// Write the `--manifest`, sorted by source path so it's the same across runs.
+fn write_manifest(path: &Path, mut entries: Vec<ManifestEntry>) -> anyhow::Result<()> {
+    entries.sort_by(|a, b| a.source.cmp(&b.source));
+    let files = entries
+        .into_iter()
+        .map(|entry| {
+            serde_json::json!({
+                "source": slash_path(&entry.source),
+                "output": slash_path(&entry.output),
+                "modules": entry.modules,
+                "attributes": entry.attributes,
+            })
+        })
+        .collect::<Vec<_>>();
+    let text = serde_json::to_string_pretty(&serde_json::json!({ "files": files }))?;
+    std::fs::write(path, format!("{text}\n"))
+        .with_context(|| format!("couldn't write {}", path.display()))
+}
+
+/// `path` to a source file as a dotted module name, like `pkg.foo` for `pkg/foo.lua` or
+/// `pkg/foo/init.lua`. A leading `lua/`, where Neovim plugins keep their modules, is left
+/// out.
+pub fn dotted_module_name(path: &Path) -> String {
+    let path = path.with_extension("");
+    let mut parts = path
+        .components()
+        .filter_map(|comp| match comp {
+            std::path::Component::Normal(part) => Some(part.to_string_lossy().to_string()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    if parts.len() > 1 && parts[0] == "lua" {
+        parts.remove(0);
+    }
+    if parts.len() > 1 && parts.last().is_some_and(|part| part == "init") {
+        parts.pop();
+    }
+    parts.join(".")
+}
+
+/// `path` with `/` between components on every platform.
+fn slash_path(path: &Path) -> String {
+    path.components()
+        .map(|comp| comp.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Whether to succeed, which is not if there were any warnings and `--fail-on-warnings` was passed, or if there were
+/// `mismatches` between `@param`s and parameters and `--strict` was passed.
+fn check_warnings(args: &Args, mismatches: usize) -> bool {
+    if args.strict && mismatches > 0 {
+        eprintln!("Failing because of {mismatches} @param mismatch(es)");
+        return false;
+    }
+    let count = diag::warning_count();
+    if args.fail_on_warnings && count > 0 {
+        eprintln!("Failing because of {count} warning(s)");
+        return false;
+    }
+    true
+}
+
+/// Print a table of how many of each attribute were converted per file, plus a total.
+fn print_report(parsed: &[(&SourceFile, Vec<Chunk>)], args: &Args) {
+    let mut total = BTreeMap::<&str, usize>::new();
+    let mut rows = Vec::<(String, BTreeMap<&str, usize>)>::new();
+
+    for (file, chunks) in parsed.iter() {
+        let mut counts = BTreeMap::<&str, usize>::new();
+        for attr in chunks.iter().flat_map(|chunk| chunk.attrs()) {
+            *counts.entry(attr.tag()).or_default() += 1;
+            *total.entry(attr.tag()).or_default() += 1;
+        }
+        let path = args.relative_path(&file.path);
+        rows.push((path.display().to_string(), counts));
+    }
+    rows.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    let name_width = rows
+        .iter()
+        .map(|(path, _)| path.len())
+        .chain(["total".len()])
+        .max()
+        .unwrap_or_default();
+
+    let mut header = format!("{:name_width$}", "file");
+    for tag in total.keys() {
+        header.push_str(&format!("  {tag:>9}"));
+    }
+    println!("{header}");
+
+    let print_row = |name: &str, counts: &BTreeMap<&str, usize>| {
+        let mut row = format!("{name:name_width$}");
+        for tag in total.keys() {
+            row.push_str(&format!(
+                "  {:>9}",
+                counts.get(tag).copied().unwrap_or_default()
+            ));
+        }
+        if counts.is_empty() {
+            row.push_str("  (no attributes found)");
+        }
+        println!("{row}");
+    };
+
+    for (path, counts) in rows.iter() {
+        print_row(path, counts);
+    }
+    print_row("total", &total);
+}
+
+/// Find all the `.lua` files under every `--path`, and `.md` files with `--from-markdown`.
+fn walk_paths(args: &Args, out_dir: &Path) -> Vec<PathBuf> {
+    let mut paths = Vec::<PathBuf>::new();
+    // Doesn't exist yet with --check, then any directory named like it is skipped instead
+    let canonical_out_dir = out_dir.canonicalize().ok();
+
+    for root in args.path.iter() {
+        if root.is_file() {
+            // Only one file to do, no need to walk anything
+            paths.push(root.clone());
+            continue;
+        }
+
+        // The output only needs to be skipped if it's actually inside the walked tree.
+        // A followed symlink can lead into it from anywhere, so always skip it then.
+        let out_dir_nested = args.follow_symlinks
+            || match (&canonical_out_dir, root.canonicalize()) {
+                (Some(out_dir), Ok(path)) => out_dir.starts_with(path),
+                _ => true,
+            };
+        let is_out_dir = |entry: &walkdir::DirEntry| match &canonical_out_dir {
+            // Compared by path so the output is skipped however it's reached, like through a
+            // followed symlink, without skipping other directories that share its name
+            Some(out_dir) => {
+                entry.file_type().is_dir()
+                    && entry
+                        .path()
+                        .canonicalize()
+                        .is_ok_and(|path| path == *out_dir)
+            }
+            None => entry.file_name() == OUTPUT_DIR,
+        };
+
+        // Sorted so the output doesn't depend on directory listing order
+        for entry in WalkDir::new(root)
+            .follow_links(args.follow_symlinks)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(|entry| !out_dir_nested || !is_out_dir(entry))
+        {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(err) if err.loop_ancestor().is_some() => {
+                    let path = err.path().unwrap_or(root);
+                    let ancestor = err.loop_ancestor().expect("checked above");
+                    diag::error(
+                        path,
+                        None,
+                        diag::Kind::Input,
+                        &format!("symlink loop back to {}, skipping", ancestor.display()),
+                    );
+                    continue;
+                }
+                Err(err) => {
+                    eprintln!("Failed to get entry: {err}");
+                    continue;
+                }
+            };
+
+            if !(entry.file_type().is_file() && args.is_source(entry.path())) {
+                continue;
+            }
+
+            paths.push(entry.into_path());
+        }
+    }
+
+    paths
+}
+
+fn read_manifest(manifest: &Path, args: &Args) -> anyhow::Result<Vec<PathBuf>> {
+    let contents = if manifest == Path::new("-") {
+        std::io::read_to_string(std::io::stdin())?
+    } else {
+        std::fs::read_to_string(manifest)?
+    };
+
+    let mut paths = Vec::new();
+    for line in contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+    {
+        let path = PathBuf::from(line);
+        if !path.is_file() {
+            diag::warn(
+                &path,
+                None,
+                diag::Kind::Input,
+                "file doesn't exist, skipping",
+            );
+        } else if !args.is_source(&path) {
+            let message = if args.from_markdown {
+                "not a .lua or .md file, skipping"
+            } else {
+                "not a .lua file, skipping"
+            };
+            diag::warn(&path, None, diag::Kind::Input, message);
+        } else {
+            paths.push(path);
+        }
+    }
+
+    Ok(paths)
+}
+
+/// Pipe the generated `text` for `path` through the `--post-process` command, run by the
+/// shell, and return what it prints. The command gets the output path in `LDOC_GEN_FILE`.
+fn run_post_process(command: &str, path: &Path, text: String) -> anyhow::Result<String> {
+    let (shell, flag) = if cfg!(windows) {
+        ("cmd", "/C")
+    } else {
+        ("sh", "-c")
+    };
+    let mut child = Command::new(shell)
+        .args([flag, command])
+        .env("LDOC_GEN_FILE", path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("couldn't run `{command}`"))?;
+
+    // Written from another thread so a command that prints before reading everything
+    // can't fill up its stdout and block us both
+    let mut stdin = child.stdin.take().expect("stdin is piped");
+    let writer = std::thread::spawn(move || stdin.write_all(text.as_bytes()));
+    let output = child
+        .wait_with_output()
+        .with_context(|| format!("couldn't run `{command}`"))?;
+    // The command doesn't have to read all of its input
+    let _ = writer.join();
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "`{command}` failed with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    String::from_utf8(output.stdout).with_context(|| format!("`{command}` printed invalid UTF-8"))
+}
+
+/// Give generated `text` consistent spacing: no trailing whitespace, at most one blank line
+/// in a row, none at the start or end, and a single newline at the end.
+fn normalize_whitespace(text: &str) -> String {
+    let mut ret = String::new();
+    let mut blank = false;
+    for line in text.lines().map(str::trim_end) {
+        if line.is_empty() {
+            blank = true;
+            continue;
+        }
+        if blank && !ret.is_empty() {
+            ret.push('\n');
+        }
+        blank = false;
+        ret.push_str(line);
+        ret.push('\n');
+    }
+    ret
+}
+
+/// Whether the file with the syntax tree `root` ends by returning a table, like a module.
+fn returns_table(root: Node) -> bool {
+    returned_table(root).is_some()
+}
+
+/// The name of the table the file ends by returning, like `M` for `return M`.
+fn returned_name<'a>(root: Node, source: &'a [u8]) -> Option<&'a str> {
+    returned_table(root)
+        .filter(|value| value.kind() == "identifier")?
+        .utf8_text(source)
+        .ok()
+}
+
+/// Whether `chunk` documents the `return M` at the end of a file, which describes the
+/// module rather than the line itself.
+fn documents_return(chunk: &Chunk, source: &[u8]) -> bool {
+    let Declaration::Other(node) = chunk.decl else {
+        return false;
+    };
+    node.kind() == "return_statement"
+        && node
+            .parent()
+            .filter(|root| root.parent().is_none())
+            .is_some_and(|root| returned_name(root, source).is_some())
+}
+
+/// Move the docs on the `return M` at the end of the file to the `@class` on `M`, since
+/// they describe the module. See [`documents_return`].
+fn attach_return_docs(file: &SourceFile, chunks: &mut Vec<Chunk>) {
+    let source = file.contents.as_bytes();
+    let Some(returned) = returned_name(file.tree.root_node(), source) else {
+        return;
+    };
+    let Some(class) = chunks
+        .iter()
+        .position(|chunk| chunk.is_class() && chunk.decl.name() == Some(returned))
+    else {
+        return;
+    };
+    let Some(doc) = chunks
+        .iter()
+        .position(|chunk| documents_return(chunk, source))
+    else {
+        return;
+    };
+
+    let class = if class > doc { class - 1 } else { class };
+    let doc = chunks.remove(doc);
+    let class = &mut chunks[class];
+    if !class.body.is_empty() && !doc.body.is_empty() {
+        class.body.push(crate::attr::doc_prefix().to_string());
+    }
+    class.body.extend(doc.body);
+    class.attributes.extend(doc.attributes);
+}
+
+/// What the file ends by returning, if it's a single table or identifier.
+fn returned_table(root: Node) -> Option<Node> {
+    let mut cursor = root.walk();
+    let ret = root
+        .children(&mut cursor)
+        .filter(|child| child.kind() != "comment")
+        .last()
+        .filter(|child| child.kind() == "return_statement")?;
+
+    let mut cursor = ret.walk();
+    let values = ret
+        .children(&mut cursor)
+        .find(|child| child.kind() == "expression_list")
+        .map(|exprs| exprs.named_children(&mut exprs.walk()).collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    match values.as_slice() {
+        [value] if matches!(value.kind(), "identifier" | "table_constructor") => Some(*value),
+        _ => None,
+    }
+}
+
+/// Remove everything in `out_dir` that wasn't just written, along with any directories
+/// left empty.
+fn clean_output(out_dir: &Path, written: &HashSet<PathBuf>) -> anyhow::Result<()> {
+    // contents_first so directories come after what's in them
+    for entry in WalkDir::new(out_dir).min_depth(1).contents_first(true) {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_type().is_dir() {
+            if std::fs::read_dir(path)?.next().is_none() {
+                std::fs::remove_dir(path)?;
+            }
+        } else if !written.contains(path) {
+            std::fs::remove_file(path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Read and parse the file at `path`, returning `None` if it couldn't be parsed.
+fn read_source_file(
+    parser: &mut tree_sitter::Parser,
+    path: PathBuf,
+    args: &Args,
+    previous: Option<&(String, Tree)>,
+) -> anyhow::Result<Option<SourceFile>> {
+    let bytes = stats::time(Phase::Read, || std::fs::read(&path))?;
+    // Tree-sitter parses exactly these bytes, so anything that changes offsets has to happen
+    // before parsing. `?` in types is turned into `|nil` when rendering instead.
+    let mut contents = match String::from_utf8(bytes) {
+        Ok(contents) => contents,
+        Err(err) => {
+            let bytes = err.as_bytes();
+            let valid = err.utf8_error().valid_up_to();
+            let line = bytes[..valid].iter().filter(|&&b| b == b'\n').count() + 1;
+            if !args.lossy_utf8 {
+                diag::warn(
+                    &path,
+                    Some(line),
+                    diag::Kind::Input,
+                    "not valid UTF-8, skipping",
+                );
+                return Ok(None);
+            }
+            diag::warn(
+                &path,
+                Some(line),
+                diag::Kind::Input,
+                "not valid UTF-8, replacing the invalid bytes",
+            );
+            String::from_utf8_lossy(bytes).into_owned()
+        }
+    };
+
+    if args.from_markdown && path.extension().is_some_and(|ext| ext == "md") {
+        contents = lua_from_markdown(&contents);
+    }
+
+    if args.two_dash {
+        stats::time(Phase::Regex, || {
+            crate::attr::promote_two_dash(&mut contents)
+        });
+    }
+
+    let aliases = stats::time(Phase::Regex, || crate::attr::extract_alias(&mut contents))
+        .into_iter()
+        .filter_map(|alias| match alias {
+            Attribute::Alias { name, members } => Some((name, members)),
+            _ => None,
+        })
+        .collect();
+
+    // Anything the alias regex didn't catch is removed so it doesn't leak into the docs
+    let leftover = stats::time(Phase::Regex, || crate::attr::leftover_aliases(&contents));
+    for line in leftover.into_iter().rev() {
+        diag::warn(
+            &path,
+            Some(line + 1),
+            diag::Kind::Alias,
+            "couldn't parse @alias, ignoring it",
+        );
+        let start = contents
+            .split_inclusive('\n')
+            .take(line)
+            .map(str::len)
+            .sum::<usize>();
+        let end = start
+            + contents[start..]
+                .find('\n')
+                .unwrap_or(contents.len() - start);
+        contents.replace_range(start..end, "");
+    }
+
+    // Only what changed since the last --watch run is parsed again
+    let previous = previous.map(|(old, tree)| {
+        let mut tree = tree.clone();
+        tree.edit(&source_edit(old, &contents));
+        tree
+    });
+    let Some(tree) = stats::time(Phase::Parse, || parser.parse(&contents, previous.as_ref()))
+    else {
+        diag::error(&path, None, diag::Kind::Parse, "failed to parse");
+        return Ok(None);
+    };
+
+    if let Some(error) = first_error(tree.root_node()) {
+        let point = error.range().start_point;
+        diag::error(
+            &path,
+            Some(point.row + 1),
+            diag::Kind::Syntax,
+            &format!(
+                "syntax error at column {}{}",
+                point.column + 1,
+                if args.best_effort { "" } else { ", skipping" }
+            ),
+        );
+        if !args.best_effort {
+            return Ok(None);
+        }
+    }
+
+    Ok(Some(SourceFile {
+        path,
+        contents,
+        tree,
+        aliases,
+    }))
+}
+
+/// The edit that turns `old` into `new`, as one changed range between the start and end
+/// they have in common.
+fn source_edit(old: &str, new: &str) -> InputEdit {
+    let start = old
+        .bytes()
+        .zip(new.bytes())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let end = old
+        .bytes()
+        .rev()
+        .zip(new.bytes().rev())
+        .take(old.len().min(new.len()) - start)
+        .take_while(|(a, b)| a == b)
+        .count();
+    InputEdit {
+        start_byte: start,
+        old_end_byte: old.len() - end,
+        new_end_byte: new.len() - end,
+        start_position: point_at(old, start),
+        old_end_position: point_at(old, old.len() - end),
+        new_end_position: point_at(new, new.len() - end),
+    }
+}
+
+/// The row and byte column of `offset` in `text`.
+fn point_at(text: &str, offset: usize) -> Point {
+    let before = &text.as_bytes()[..offset];
+    let line_start = before
+        .iter()
+        .rposition(|&b| b == b'\n')
+        .map_or(0, |i| i + 1);
+    Point {
+        row: before.iter().filter(|&&b| b == b'\n').count(),
+        column: offset - line_start,
+    }
+}
+
+/// The code in the ```` ```lua ```` blocks of a Markdown file, for `--from-markdown`.
+/// Every other line is blanked instead of removed, so line numbers in warnings and errors
+/// are the Markdown file's.
+fn lua_from_markdown(markdown: &str) -> String {
+    let mut ret = String::with_capacity(markdown.len());
+    // The opening fence's character and length while in a Lua block
+    let mut fence: Option<(char, usize)> = None;
+    for line in markdown.split_inclusive('\n') {
+        let text = line.trim_end_matches(['\n', '\r']);
+        let ending = &line[text.len()..];
+        let trimmed = text.trim_start_matches(' ');
+        // Fences can be indented by up to three spaces, more is an indented code block
+        let indent = text.len() - trimmed.len();
+        let marker = trimmed.chars().next().filter(|c| *c == '`' || *c == '~');
+        let marker_len = marker.map_or(0, |c| trimmed.len() - trimmed.trim_start_matches(c).len());
+
+        match fence {
+            Some((c, len)) => {
+                let closes = indent < 4
+                    && marker == Some(c)
+                    && marker_len >= len
+                    && trimmed[marker_len..].trim().is_empty();
+                if closes {
+                    fence = None;
+                } else {
+                    ret.push_str(text);
+                }
+            }
+            None => {
+                let lang = marker
+                    .filter(|_| indent < 4 && marker_len >= 3)
+                    .and_then(|_| trimmed[marker_len..].split_whitespace().next());
+                if let (Some(c), Some(lang)) = (marker, lang) {
+                    if lang.eq_ignore_ascii_case("lua") {
+                        fence = Some((c, marker_len));
+                    }
+                }
+            }
+        }
+        ret.push_str(ending);
+    }
+    ret
+}
+
+/// Find the first ERROR or MISSING node in this tree.
+fn first_error(node: Node) -> Option<Node> {
+    if node.is_error() || node.is_missing() {
+        return Some(node);
+    }
+    if !node.has_error() {
+        return None;
+    }
+
+    let mut cursor = node.walk();
+    let children = node.children(&mut cursor).collect::<Vec<_>>();
+    children.into_iter().find_map(first_error)
+}
+
+/// A parsed source file.
+pub struct SourceFile {
+    pub path: PathBuf,
+    /// The preprocessed source that `tree` was parsed from
+    pub contents: String,
+    pub tree: Tree,
+    /// The `@alias`es extracted from the source, by name
+    pub aliases: HashMap<String, Vec<AliasMember>>,
+}
+
+/// Render a file's chunks with `renderer`, putting each class's members after it.
+///
+/// `module` is the table the file returns. Its members go first, so they aren't mixed
+/// into the last class when it isn't a class itself. `merged` holds the already rendered
+/// members of classes from other files for `--merge-modules`, which go after the class's
+/// own. `class` is the class of members whose declaration isn't in `chunks`, which is
+/// only the case when rendering those.
+fn render_chunks(
+    chunks: &[Chunk],
+    source: &[u8],
+    module: Option<&str>,
+    class: Option<&str>,
+    mut merged: HashMap<String, String>,
+    renderer: &dyn Renderer,
+    args: &Args,
+) -> String {
+    // The class each table is declared as, for the members grouped under it
+    let classes = chunks
+        .iter()
+        .filter(|chunk| chunk.is_class())
+        .filter_map(|chunk| Some((chunk.name()?, chunk.class_name()?)))
+        .collect::<HashMap<_, _>>();
+    let render = |chunk: &Chunk| {
+        let group = chunk
+            .decl
+            .group()
+            .and_then(|group| classes.get(group).copied());
+        renderer.render_chunk(chunk, chunk.class_name().or(group).or(class), source)
+    };
+
+    // With --infer-module, the docs on `return M` go with the inferred `@module` instead.
+    // Otherwise they go first on their own, where LDoc takes them as the module's description.
+    let inferred = args.infer_module && !chunks.iter().any(Chunk::is_class);
+    let documented = chunks
+        .iter()
+        .filter(|chunk| !chunk.attrs().any(|attr| matches!(attr, Attribute::NoDoc)))
+        .filter(|chunk| !(args.no_locals && chunk.is_local()));
+    let module_doc = documented
+        .clone()
+        .find(|chunk| !inferred && documents_return(chunk, source))
+        .map(|doc| {
+            render(&Chunk {
+                body: doc.body.clone(),
+                attributes: doc.attributes.clone(),
+                decl: Declaration::Dangling,
+            })
+        })
+        .unwrap_or_default();
+    let documented = documented.filter(|chunk| !documents_return(chunk, source));
+
+    if args.preserve_order {
+        let mut ldoc_text = module_doc;
+        for chunk in documented {
+            ldoc_text.push_str(&render(chunk));
+            if let Some(text) = chunk.class_name().and_then(|class| merged.remove(class)) {
+                ldoc_text.push_str(&text);
+            }
+        }
+        return ldoc_text;
+    }
+
+    let (mut mods_and_classes, rest): (Vec<_>, _) = documented.partition(|chunk| chunk.is_class());
+    // `@type`s are documented as part of the file's `@classmod`, so they go after it
+    mods_and_classes.sort_by_key(|chunk| {
+        chunk
+            .attrs()
+            .any(|attr| matches!(attr, Attribute::ClassMod { is_module: false }))
+    });
+
+    // Lookup only; output order comes from `mods_and_classes` and the order chunks are pushed
+    let mut methods = HashMap::<&str, Vec<&Chunk>>::new();
+    const NO_NAME: &str = "_NO_NAME";
+    methods.insert(NO_NAME, vec![]);
+
+    for chunk in mods_and_classes.iter() {
+        let Some(name) = chunk.name() else {
+            continue;
+        };
+        methods.insert(name, vec![]);
+    }
+
+    // Only if it isn't already a class, which handles its own members
+    let module = module.filter(|module| !methods.contains_key(module));
+    if let Some(module) = module {
+        methods.insert(module, vec![]);
+    }
+
+    for chunk in rest.iter() {
+        if let Some(name) = chunk.decl.group() {
+            if let Some(v) = methods.get_mut(name) {
+                v.push(chunk);
+            } else {
+                methods.get_mut(NO_NAME).unwrap().push(chunk);
+            }
+        } else {
+            methods.get_mut(NO_NAME).unwrap().push(chunk);
+        }
+    }
+
+    if args.sort == SortOrder::Alpha {
+        // Stable, so ties keep source order. Chunks without a name go last.
+        for chunks in methods.values_mut() {
+            chunks.sort_by_cached_key(|chunk| {
+                let name = chunk.decl.member_name(source);
+                (name.is_none(), name)
+            });
+        }
+    }
+
+    // Keep each `@within` section together, after the chunks in the default section
+    for chunks in methods.values_mut() {
+        let mut sections = Vec::<&str>::new();
+        for chunk in chunks.iter() {
+            if let Some(section) = chunk.section() {
+                if !sections.contains(&section) {
+                    sections.push(section);
+                }
+            }
+        }
+        chunks.sort_by_key(|chunk| {
+            chunk
+                .section()
+                .and_then(|section| sections.iter().position(|s| *s == section))
+                .map_or(0, |i| i + 1)
+        });
+    }
+
+    let mut ldoc_text = module_doc;
+
+    if let Some(chunks) = module.and_then(|module| methods.remove(module)) {
+        for chunk in chunks.iter() {
+            ldoc_text.push_str(&render(chunk));
+        }
+    }
+
+    // We have to place functions in a module/class in sections under the
+    // corresponding LDoc annotation. The loop below orders functions correctly
+    // as to not screw up LDoc generation.
+    for chunk in mods_and_classes {
+        let Some(name) = chunk.name() else {
+            continue;
+        };
+
+        ldoc_text.push_str(&render(chunk));
+        // Removed so a class declared twice doesn't get its methods twice
+        if let Some(chunks) = methods.remove(name) {
+            for chunk in chunks.iter() {
+                ldoc_text.push_str(&render(chunk));
+            }
+        }
+        if let Some(text) = chunk.class_name().and_then(|class| merged.remove(class)) {
+            ldoc_text.push_str(&text);
+        }
+    }
+
+    for chunk in methods.get(NO_NAME).unwrap() {
+        ldoc_text.push_str(&render(chunk));
+    }
+
+    ldoc_text
+}
+
+/// Warn about and remove `@return`s without a type that didn't have a typed `@return`
+/// before them to describe, which are left in the description by
+/// [`attributes_from_comment_block`].
+fn warn_typeless_returns(file: &SourceFile, chunks: &mut [Chunk]) {
+    for chunk in chunks.iter_mut() {
+        let line = match &chunk.decl {
+            Declaration::Function(_, node)
+            | Declaration::Variable(_, node)
+            | Declaration::Target(_, node, _)
+            | Declaration::Other(node) => Some(node.range().start_point.row + 1),
+            Declaration::Dangling => None,
+        };
+        chunk.body.retain(|text| {
+            if crate::attr::typeless_return(text).is_none() {
+                return true;
+            }
+            // Body lines don't keep their line number, so find it in the doc block above
+            let line = line.map(|line| {
+                let lines = file.contents.lines().take(line - 1).collect::<Vec<_>>();
+                lines
+                    .into_iter()
+                    .enumerate()
+                    .rev()
+                    .take_while(|(_, source)| source.trim_start().starts_with("--"))
+                    .find(|(_, source)| source.trim() == text.trim())
+                    .map_or(line, |(i, _)| i + 1)
+            });
+            diag::warn(
+                &file.path,
+                line,
+                diag::Kind::TypelessReturn,
+                &format!("@return has no type, ignoring it: `{}`", text.trim()),
+            );
+            false
+        });
+    }
+}
+
+/// Work out which `@classmod` class is the file's module: the one on the table the file
+/// returns, or the first class if it doesn't return one, like a stub file. The others are
+/// documented as a `@type` instead, since LDoc takes `@classmod` as the file's module.
+fn mark_classmod_modules(file: &SourceFile, chunks: &mut [Chunk]) {
+    let returned = returned_name(file.tree.root_node(), file.contents.as_bytes());
+    let mut first = true;
+    for chunk in chunks.iter_mut().filter(|chunk| chunk.is_class()) {
+        let is_module = match returned {
+            Some(returned) => chunk.decl.name() == Some(returned),
+            None => first,
+        };
+        first = false;
+        for attr in chunk.attrs_mut() {
+            if let Attribute::ClassMod { is_module: module } = attr {
+                *module = is_module;
+            }
+        }
+    }
+}
+
+/// Move the `@field`s of each class to its first declaration in `chunks`, right after the
+/// `@class`, for `--fields-at-class`. They keep their order, and everything else stays
+/// where it was.
+fn gather_class_fields(chunks: &mut [Chunk]) {
+    let mut fields = HashMap::<String, Vec<Spanned<Attribute>>>::new();
+    for chunk in chunks.iter_mut().filter(|chunk| chunk.is_class()) {
+        let Some(class) = chunk.class_name().map(str::to_string) else {
+            continue;
+        };
+        let (moved, rest): (Vec<_>, _) = std::mem::take(&mut chunk.attributes)
+            .into_iter()
+            .partition(|attr| matches!(attr.value, Attribute::Field { .. }));
+        chunk.attributes = rest;
+        fields.entry(class).or_default().extend(moved);
+    }
+
+    for chunk in chunks.iter_mut() {
+        let Some(class) = chunk.class_name() else {
+            continue;
+        };
+        // Only the first declaration is still in the map
+        let Some(fields) = fields.remove(class) else {
+            continue;
+        };
+        let after = chunk
+            .attrs()
+            .position(|attr| matches!(attr, Attribute::Class { .. }))
+            .map_or(0, |i| i + 1);
+        chunk.attributes.splice(after..after, fields);
+    }
+}
+
+/// Add the example for each module and class in `chunks` from `dir` as a `@usage`, for
+/// `--examples-dir`. Only the first declaration of each gets it.
+fn add_examples(dir: &Path, chunks: &mut [Chunk]) -> anyhow::Result<()> {
+    let mut done = HashSet::<String>::new();
+    for chunk in chunks.iter_mut() {
+        let Some(class) = chunk.class_name().map(str::to_string) else {
+            continue;
+        };
+        if !done.insert(class.clone()) {
+            continue;
+        }
+        if let Some(usage) = example_usage(dir, &class)? {
+            chunk.attributes.push(usage.into());
+        }
+    }
+    Ok(())
+}
 
-mod attr;
-mod chunk;
+/// The example for `module` in `dir` as a `@usage`, from `dir/foo.bar.lua` or
+/// `dir/foo/bar.lua` for `foo.bar`. Modules without one don't get a `@usage`.
+fn example_usage(dir: &Path, module: &str) -> anyhow::Result<Option<Attribute>> {
+    let candidates = [
+        dir.join(format!("{module}.lua")),
+        dir.join(module.replace('.', "/")).with_extension("lua"),
+    ];
+    for path in candidates {
+        let code = match std::fs::read_to_string(&path) {
+            Ok(code) => code,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(err) => {
+                return Err(err).with_context(|| format!("couldn't read {}", path.display()))
+            }
+        };
+        if code.trim().is_empty() {
+            return Ok(None);
+        }
+        // Indented as if they were on their own `--- ` lines, like a `@usage` in a comment
+        let lines = code
+            .trim_end()
+            .lines()
+            .map(|line| format!(" {line}"))
+            .collect();
+        return Ok(Some(Attribute::Usage { lines }));
+    }
+    Ok(None)
+}
 
-use std::{collections::HashMap, path::PathBuf};
+/// Move the members of classes declared in more than one file into the first file that
+/// declares them, for `--merge-modules`. The other files lose those classes, and their
+/// `@field`s go to the first file's declaration.
+///
+/// Members are rendered here since they need their own file's source. The returned text
+/// for each file, by class name, should be passed to [`render_chunks`].
+fn merge_modules(
+    parsed: &mut [(&SourceFile, Vec<Chunk>)],
+    renderer: &dyn Renderer,
+    args: &Args,
+) -> Vec<HashMap<String, String>> {
+    let mut owners = HashMap::<String, usize>::new();
+    for (i, (_, chunks)) in parsed.iter().enumerate() {
+        for class in chunks.iter().filter(|chunk| chunk.is_class()) {
+            if let Some(class) = class.class_name() {
+                owners.entry(class.to_string()).or_insert(i);
+            }
+        }
+    }
 
-use attr::{Attribute, ATTR_REGEXES};
-use chunk::Chunk;
-use clap::Parser;
-use pcre2::bytes::Regex;
-use tree_sitter::{Node, TreeCursor};
-use walkdir::WalkDir;
+    let mut merged = vec![HashMap::<String, String>::new(); parsed.len()];
+    // (owner, class, fields)
+    let mut fields = Vec::<(usize, String, Vec<Attribute>)>::new();
+    for (i, (file, chunks)) in parsed.iter_mut().enumerate() {
+        let source = file.contents.as_bytes();
+        let mut classes = chunks
+            .iter()
+            .filter(|chunk| chunk.is_class())
+            .filter_map(Chunk::class_name)
+            .filter(|class| owners[*class] != i)
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        classes.dedup();
 
-const OUTPUT_DIR: &str = ".ldoc_gen";
+        for class in classes {
+            let owner = owners[&class];
+            let (decls, rest): (Vec<_>, _) = std::mem::take(chunks)
+                .into_iter()
+                .partition(|chunk| chunk.is_class() && chunk.class_name() == Some(&class));
+            let names = decls
+                .iter()
+                .filter_map(|chunk| chunk.name().map(str::to_string))
+                .collect::<Vec<_>>();
+            let (members, rest): (Vec<_>, _) = rest.into_iter().partition(|chunk| {
+                !chunk.is_class()
+                    && chunk
+                        .decl
+                        .group()
+                        .is_some_and(|group| names.iter().any(|name| name == group))
+            });
+            *chunks = rest;
 
-fn main() -> anyhow::Result<()> {
-    let args = Args::parse();
+            fields.push((
+                owner,
+                class.clone(),
+                decls
+                    .into_iter()
+                    .flat_map(|decl| decl.attributes)
+                    .map(|attr| attr.value)
+                    .filter(|attr| matches!(attr, Attribute::Field { .. }))
+                    .collect(),
+            ));
+            for name in names {
+                let text = render_chunks(
+                    &members,
+                    source,
+                    Some(&name),
+                    Some(&class),
+                    HashMap::new(),
+                    renderer,
+                    args,
+                );
+                merged[owner]
+                    .entry(class.clone())
+                    .or_default()
+                    .push_str(&text);
+            }
+        }
+    }
 
-    let mut parser = tree_sitter::Parser::new();
-    parser.set_language(tree_sitter_lua::language())?;
+    for (owner, class, fields) in fields {
+        let decl = parsed[owner]
+            .1
+            .iter_mut()
+            .find(|chunk| chunk.is_class() && chunk.class_name() == Some(&class));
+        if let Some(decl) = decl {
+            // Their lines are in another file
+            decl.attributes
+                .extend(fields.into_iter().map(Spanned::from));
+        }
+    }
 
-    let out_dir = args.out_dir.join(OUTPUT_DIR);
+    merged
+}
 
-    std::fs::create_dir_all(&out_dir)?;
+/// `--version` output, with the grammar and tree-sitter ABI versions to help triage
+/// parsing discrepancies.
+static LONG_VERSION: LazyLock<String> = LazyLock::new(|| {
+    format!(
+        "{}\ntree-sitter-lua {} (grammar ABI {})\ntree-sitter ABI {} (min compatible {})",
+        env!("CARGO_PKG_VERSION"),
+        env!("TREE_SITTER_LUA_VERSION"),
+        tree_sitter_lua::language().version(),
+        tree_sitter::LANGUAGE_VERSION,
+        tree_sitter::MIN_COMPATIBLE_LANGUAGE_VERSION,
+    )
+});
 
-    for entry in WalkDir::new(&args.path).into_iter().filter_entry(|entry| {
-        // skip output_dir
-        entry.file_name() != OUTPUT_DIR
-    }) {
-        let entry = match entry {
-            Ok(entry) => entry,
-            Err(err) => {
-                eprintln!("Failed to get entry: {err}");
-                continue;
-            }
+#[derive(Parser, Debug)]
+#[command(author, version, long_version = LONG_VERSION.as_str(), about, long_about = None)]
+struct Args {
+    /// A file or directory to convert. Can be given more than once, in which case the
+    /// output for each one goes in its own subdirectory named after it
+    #[arg(short, long, default_value = ".")]
+    path: Vec<PathBuf>,
+    #[arg(short, long, default_value_os_t = PathBuf::from("."))]
+    out_dir: PathBuf,
+    /// Descend into function bodies to pick up documented `self.<field>` assignments and
+    /// `@class`es declared inside functions
+    #[arg(long)]
+    descend_bodies: bool,
+    /// Print the files that would be converted, one per line, without converting them
+    #[arg(long)]
+    list_files: bool,
+    /// Print more about what's being done. With `--list-files`, this is where each file's
+    /// output would be written.
+    #[arg(short, long)]
+    verbose: bool,
+    /// Write all generated docs into one file with this name in the output directory
+    #[arg(long, value_name = "NAME")]
+    single_file: Option<PathBuf>,
+    /// Print all generated docs to stdout, like `--single-file` does into a file, instead
+    /// of writing anything to the output directory. Nothing else can be printed there, so
+    /// this doesn't work with `--report`, `--stats`, or `--error-stream stdout`.
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "single_file", "emit_index", "clean", "manifest", "post_process", "report", "stats"
+        ]
+    )]
+    output_on_stdout: bool,
+    /// Keep running and convert again whenever a source file changes. Changed files are
+    /// reparsed incrementally from their previous syntax tree.
+    #[arg(long, conflicts_with_all = ["check", "list_files"])]
+    watch: bool,
+    /// Document a class declared in several files once, in the output for the first file
+    /// that declares it, with the members from all of them
+    #[arg(long)]
+    merge_modules: bool,
+    /// List the `@field`s each class inherits from its parents in `@class Foo : Bar`
+    #[arg(long)]
+    show_inherited: bool,
+    /// Put all of a class's `@field`s together right after its `@class`, instead of where
+    /// they were written
+    #[arg(long)]
+    fields_at_class: bool,
+    /// Turn references to known modules, classes, and functions in descriptions, like
+    /// `` `Foo.bar` ``, into LDoc `@{Foo.bar}` links
+    #[arg(long)]
+    link_inline_refs: bool,
+    /// Write `@see`s as a "See also" line at the end of the description instead of as
+    /// separate `@see` tags
+    #[arg(long)]
+    inline_see: bool,
+    /// Put declarations between `--#region Name` and `--#endregion` comments in a
+    /// `@within Name` section
+    #[arg(long)]
+    regions_as_sections: bool,
+    /// Include private, protected, and package `@field`s in the output
+    #[arg(long)]
+    include_private: bool,
+    /// Leave out `local` functions and ones marked `@private` or `@package`, instead of
+    /// documenting them as `@local`
+    #[arg(long)]
+    no_locals: bool,
+    /// Don't show progress while processing files
+    #[arg(long)]
+    no_progress: bool,
+    /// Don't print progress, warnings, or other non-error output. Output asked for with
+    /// flags like `--report` and `--stats` is still printed
+    #[arg(short, long)]
+    quiet: bool,
+    /// Print how many of each attribute were converted per file
+    #[arg(long)]
+    report: bool,
+    /// The extension of generated files, e.g. `ld.lua` to write `foo.ld.lua`.
+    /// Defaults to `lua`, or `md` with `--format markdown`.
+    #[arg(long)]
+    out_ext: Option<String>,
+    /// Keep converting files with syntax errors as best as possible instead of skipping them
+    #[arg(long)]
+    best_effort: bool,
+    /// Convert files that aren't valid UTF-8, like ones with Latin-1 comments, with the
+    /// invalid bytes replaced instead of skipping them
+    #[arg(long)]
+    lossy_utf8: bool,
+    /// Also convert the ```` ```lua ```` code blocks in `.md` files, as if each file's blocks
+    /// were one Lua file named after it
+    #[arg(long)]
+    from_markdown: bool,
+    /// How to report errors and warnings about files
+    #[arg(long, value_enum, default_value_t)]
+    error_format: ErrorFormat,
+    /// Where to report errors and warnings about files
+    #[arg(long, value_enum, default_value_t)]
+    error_stream: ErrorStream,
+    /// Markdown headings whose code blocks are converted into `@usage`
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_values_t = ["Example".to_string(), "Examples".to_string(), "Usage".to_string()]
+    )]
+    example_headings: Vec<String>,
+    /// Also write an `index` page listing every generated module and class
+    #[arg(long)]
+    emit_index: bool,
+    /// Write a JSON list of the files generated, with the source, modules, and number of
+    /// attributes of each, to this path
+    #[arg(long, value_name = "PATH")]
+    manifest: Option<PathBuf>,
+    /// Pipe each generated file through this shell command before writing it. It gets the
+    /// file's content on stdin and the path in `LDOC_GEN_FILE`, and what it prints is
+    /// written instead
+    #[arg(long, value_name = "CMD")]
+    post_process: Option<String>,
+    /// Start each generated file with a comment saying which source file it's from.
+    /// `--single-file` output already notes this for each file.
+    #[arg(long)]
+    emit_source_comment: bool,
+    /// Print how long each phase of the run took
+    #[arg(long)]
+    stats: bool,
+    /// Print which regexes were tried on annotation lines that didn't match any, and how
+    /// far they got. For debugging the regexes.
+    #[arg(long, hide = true)]
+    trace_regex: bool,
+    /// Exit with an error if there were any warnings, after converting everything
+    #[arg(long)]
+    fail_on_warnings: bool,
+    /// Don't warn about `@param`s that don't match the function's parameters
+    #[arg(long)]
+    no_param_check: bool,
+    /// What to do with a `@param` documented more than once, after warning about it
+    #[arg(long, value_enum, default_value_t)]
+    duplicate_params: DuplicateParams,
+    /// Warn about `return`s that return a different number of values than the function
+    /// has `@return`s
+    #[arg(long)]
+    check_returns: bool,
+    /// Warn about types in `@param`s, `@return`s, and `@field`s that aren't a class or
+    /// module from the run
+    #[arg(long)]
+    check_types: bool,
+    /// Report `@param`s that don't match the function's parameters as errors, and exit
+    /// with an error after converting everything if there were any
+    #[arg(long, conflicts_with = "no_param_check")]
+    strict: bool,
+    /// Emit the summary, the first paragraph of each description, as an explicit
+    /// `@summary` instead of letting LDoc find it
+    #[arg(long)]
+    explicit_summary: bool,
+    /// Wrap descriptions, summaries, and `@tparam` and `@treturn` descriptions longer than
+    /// this many columns onto more `---` lines, which LDoc joins back together
+    #[arg(long, value_name = "N", alias = "max-line-length")]
+    wrap: Option<usize>,
+    /// Leave examples in the description instead of turning them into `@usage`
+    #[arg(long)]
+    no_usage: bool,
+    /// Add the contents of `DIR/foo.lua` (or `DIR/foo/bar.lua` for `foo.bar`) as a `@usage`
+    /// to the module or class `foo`
+    #[arg(long, value_name = "DIR")]
+    examples_dir: Option<PathBuf>,
+    /// Check that documented functions have a `@param` for every parameter and a `@return`
+    /// if they return something, exiting with an error if not. Nothing is written.
+    #[arg(long)]
+    check: bool,
+    /// How to render methods and their `self` parameter
+    #[arg(long, value_enum, default_value_t)]
+    self_style: SelfStyle,
+    /// How to write the `integer` type. Kept as `integer` by default
+    #[arg(long, value_enum)]
+    integer_as: Option<IntegerAs>,
+    /// Only convert the files listed one per line in this file (`-` for stdin) instead of
+    /// walking `--path`
+    #[arg(long, value_name = "MANIFEST")]
+    files_from: Option<PathBuf>,
+    /// Emit tags in LDoc's preferred order (params, returns, `@see`, then modifiers)
+    /// instead of source order
+    #[arg(long)]
+    normalize_order: bool,
+    /// Replace the text added to the docs (like the `@nodiscard` note) with the text
+    /// in this TOML file
+    #[arg(long, value_name = "TOML")]
+    lang: Option<PathBuf>,
+    /// Write output files for sources with nothing to document
+    #[arg(long)]
+    keep_empty: bool,
+    /// Follow symlinks when walking `--path`. Symlink loops are reported and skipped, and
+    /// so is the output directory, even when a link leads into it from outside the tree
+    #[arg(long)]
+    follow_symlinks: bool,
+    /// What doc comments start with
+    #[arg(
+        long,
+        value_name = "PREFIX",
+        default_value = "---",
+        allow_hyphen_values = true
+    )]
+    doc_prefix: String,
+    /// Also recognize annotations written with two dashes, like `--@param`. Only lines
+    /// starting with a known tag or an `@alias` continuation (`--|`) count
+    #[arg(long)]
+    two_dash: bool,
+    /// Remove files in the output directory that weren't generated by this run, like ones
+    /// for deleted sources
+    #[arg(long)]
+    clean: bool,
+    /// Add a `@module` named after the file's path (`lua/foo/bar.lua` is `foo.bar`) to
+    /// files that return a table but don't declare a `@class` or `@module`
+    #[arg(long)]
+    infer_module: bool,
+    /// What to generate
+    #[arg(long, value_enum, default_value_t)]
+    format: OutputFormat,
+    /// Prefix every module and class name with this, like `mypkg` for `mypkg.foo`
+    #[arg(long, value_name = "PREFIX")]
+    root_module: Option<String>,
+    /// How to order the functions and fields in each module
+    #[arg(long, value_enum, default_value_t)]
+    sort: SortOrder,
+    /// Write everything in source order instead of grouping members under their module
+    /// or class, with anything that isn't in one last
+    #[arg(long, conflicts_with = "sort")]
+    preserve_order: bool,
+    /// Read options from this file instead of `.ldoc_gen.toml` in the first `--path`
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+    /// Put the contents of this file at the top of every output file
+    #[arg(long, value_name = "FILE", conflicts_with = "header_text")]
+    header: Option<PathBuf>,
+    /// Put this text at the top of every output file. Without a value, this is a
+    /// "generated, do not edit" notice.
+    #[arg(long, value_name = "TEXT", num_args = 0..=1, default_missing_value = GENERATED_NOTICE)]
+    header_text: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Lua files with LDoc annotations
+    #[default]
+    Ldoc,
+    /// Markdown API reference files
+    Markdown,
+}
+
+/// How methods declared with `:` or with an explicit `self` are rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum SelfStyle {
+    /// Render methods as they were written
+    #[default]
+    Keep,
+    /// Render all methods as `function Foo:bar(x)` without a `self` param
+    Colon,
+    /// Render all methods as `function Foo.bar(self, x)` with a `self` param
+    Dot,
+}
+
+/// What happens to the repeats of a `@param` documented more than once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum DuplicateParams {
+    /// Only keep the first one
+    #[default]
+    First,
+    /// Keep all of them
+    Keep,
+}
+
+/// What the `integer` type is written as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum IntegerAs {
+    /// LDoc's `int`
+    Int,
+    /// Plain `number`, like Lua 5.1
+    Number,
+    /// `integer`, even if the config renames it
+    Integer,
+}
+
+/// How the functions and fields in each module are ordered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum SortOrder {
+    /// In the order they're declared
+    #[default]
+    Source,
+    /// Alphabetically by name
+    Alpha,
+}
+
+impl Args {
+    /// The header from `--header` or `--header-text` as plain `--` comments, so LDoc
+    /// doesn't mistake it for the module's doc comment.
+    fn header(&self) -> anyhow::Result<String> {
+        let text = match (&self.header, &self.header_text) {
+            (Some(path), _) => std::fs::read_to_string(path)
+                .with_context(|| format!("couldn't read {}", path.display()))?,
+            (None, Some(text)) => text.clone(),
+            (None, None) => return Ok(String::new()),
         };
 
-        if !(entry.file_type().is_file()
-            && entry
-                .file_name()
-                .to_string_lossy()
-                .to_string()
-                .ends_with(".lua"))
-        {
-            continue;
+        if self.format == OutputFormat::Markdown {
+            return Ok(format!("<!--\n{}\n-->\n", text.trim_end()));
         }
 
-        // Replace all ? with |nil to make LDoc happy,
-        // and remove @type to fix warnings/errors
-        let mut contents = std::fs::read_to_string(entry.path())?
-            .replace('?', "|nil")
-            .replace("@type", "");
+        let mut header = text
+            .lines()
+            .map(|line| {
+                // Lines that are already comments are fine, as long as they aren't doc comments
+                let line = match line.strip_prefix("--") {
+                    Some(rest) => rest.trim_start_matches('-').trim_start(),
+                    None => line,
+                };
+                if line.is_empty() {
+                    "--".to_string()
+                } else {
+                    format!("-- {line}")
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        header.push('\n');
+        Ok(header)
+    }
+
+    /// The path of the output file generated for the source file at `path`,
+    /// relative to the output directory.
+    ///
+    /// This mirrors the source's directory relative to its [`Args::source_root`].
+    fn output_path(&self, path: &Path) -> PathBuf {
+        let mut out_path = self.relative_path(path);
+        let stem = path.file_stem().expect("source file has no name");
+        let mut name = stem.to_os_string();
+        name.push(".");
+        name.push(self.out_ext());
+        out_path.set_file_name(name);
+        out_path
+    }
 
-        // TODO:
-        let _ = crate::attr::extract_alias(&mut contents);
+    /// Whether the file at `path` is converted, which is `.lua` files and, with
+    /// `--from-markdown`, `.md` files.
+    fn is_source(&self, path: &Path) -> bool {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("lua") => true,
+            Some("md") => self.from_markdown,
+            _ => false,
+        }
+    }
 
-        let Some(tree) = parser.parse(&contents, None) else {
-            eprintln!("Failed to parse {}", entry.file_name().to_string_lossy());
-            continue;
+    /// The extension of generated files, from `--out-ext` or the format.
+    fn out_ext(&self) -> &str {
+        self.out_ext.as_deref().unwrap_or(match self.format {
+            OutputFormat::Ldoc => "lua",
+            OutputFormat::Markdown => "md",
+        })
+    }
+
+    /// The dotted module name for the file at `path`, like `foo.bar` for `lua/foo/bar.lua`.
+    ///
+    /// A leading `lua` directory is dropped, and so is a trailing `init` like Lua's
+    /// `require` does.
+    fn module_name(&self, path: &Path) -> String {
+        let (_, rel_path) = self.path_in_root(path);
+        // Under a `[module_prefixes]` glob, names start after its directories, since the
+        // prefix takes their place
+        let rel_path = match config::module_prefix(&slash_path(&rel_path)) {
+            Some((_, base)) => rel_path
+                .strip_prefix(base)
+                .unwrap_or(&rel_path)
+                .to_path_buf(),
+            None => rel_path,
+        };
+        dotted_module_name(&rel_path)
+    }
+
+    /// `path` relative to the output's mirror of the sources. This is its path in its
+    /// [`Args::source_root`], under that root's [`Args::root_dir`] if there are several.
+    fn relative_path(&self, path: &Path) -> PathBuf {
+        match self.path_in_root(path) {
+            (Some(root), rel_path) if self.path.len() > 1 => self.root_dir(root).join(rel_path),
+            (_, rel_path) => rel_path,
+        }
+    }
+
+    /// Which `--path` `path` is under, and `path` relative to its [`Args::source_root`].
+    ///
+    /// Paths outside of all of them fall back to just their file name.
+    fn path_in_root(&self, path: &Path) -> (Option<usize>, PathBuf) {
+        for i in 0..self.path.len() {
+            if let Ok(rel_path) = path.strip_prefix(self.source_root(i)) {
+                return (Some(i), rel_path.to_path_buf());
+            }
+        }
+        // Paths from --files-from may be spelled differently from --path
+        if let Ok(path) = path.canonicalize() {
+            for i in 0..self.path.len() {
+                let Ok(root) = self.source_root(i).canonicalize() else {
+                    continue;
+                };
+                if let Ok(rel_path) = path.strip_prefix(root) {
+                    return (Some(i), rel_path.to_path_buf());
+                }
+            }
+        }
+        (
+            None,
+            PathBuf::from(path.file_name().expect("source file has no name")),
+        )
+    }
+
+    /// The directory source paths under the `i`th `--path` are relative to.
+    ///
+    /// This is the `--path` itself, or its parent if it points at a single file.
+    fn source_root(&self, i: usize) -> &Path {
+        let path = &self.path[i];
+        if path.is_file() {
+            path.parent().unwrap_or(Path::new(""))
+        } else {
+            path
+        }
+    }
+
+    /// The subdirectory of the output the `i`th `--path` goes in when there are several,
+    /// named after its [`Args::source_root`].
+    fn root_dir(&self, i: usize) -> PathBuf {
+        let root = match self.source_root(i) {
+            root if root.as_os_str().is_empty() => Path::new("."),
+            root => root,
+        };
+        root.file_name()
+            .map(PathBuf::from)
+            .or_else(|| {
+                // `.` and `..` have no name of their own
+                root.canonicalize().ok()?.file_name().map(PathBuf::from)
+            })
+            .unwrap_or_else(|| PathBuf::from(format!("root{i}")))
+    }
+}
+
+#[derive(Debug)]
+pub enum Declaration<'a> {
+    Function(Option<String>, Node<'a>),
+    Variable(String, Node<'a>),
+    /// One target out of a multi-target assignment like `a, b = 1, 2`,
+    /// holding the target and its assigned value
+    Target(String, Node<'a>, Option<Node<'a>>),
+    Other(Node<'a>),
+    /// Nothing, for a doc comment at the end of a file or a `@class` that isn't attached
+    /// to any code
+    Dangling,
+}
+
+impl Declaration<'_> {
+    /// The declared name. For functions this is the name of the table the function
+    /// is declared on, if any.
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            Declaration::Function(Some(name), _)
+            | Declaration::Variable(name, _)
+            | Declaration::Target(name, ..) => Some(name),
+            Declaration::Function(None, _) | Declaration::Other(_) | Declaration::Dangling => None,
+        }
+    }
+
+    /// The name of the declared function or field without its table, like `bar` for
+    /// `function Foo:bar()`.
+    pub fn member_name(&self, source: &[u8]) -> Option<String> {
+        let name = match self {
+            Declaration::Function(_, node) => {
+                node.child_by_field_name("name")?.utf8_text(source).ok()?
+            }
+            Declaration::Variable(name, _) | Declaration::Target(name, ..) => name,
+            Declaration::Other(_) | Declaration::Dangling => return None,
         };
+        let member = name.rsplit(['.', ':', '[']).next().unwrap_or(name);
+        Some(member.trim_end_matches(']').trim_matches('"').to_string())
+    }
+
+    /// The name used to group this declaration under a module or class.
+    ///
+    /// For fields like `Foo.bar = ...` this is the table, `Foo`.
+    pub fn group(&self) -> Option<&str> {
+        match self {
+            Declaration::Function(..) => self.name(),
+            _ => {
+                let name = self.name()?;
+                Some(name.split(['.', ':', '[']).next().unwrap_or(name))
+            }
+        }
+    }
+}
+
+/// Build chunks from the direct children of `parent`.
+///
+/// A chunk is a bunch of comments annotating some function or declaration.
+fn build_chunks<'a>(parent: Node<'a>, source: &[u8]) -> anyhow::Result<Vec<Chunk<'a>>> {
+    let mut cursor = parent.walk();
+    let children = parent.children(&mut cursor).collect::<Vec<_>>();
+    build_chunks_from(&children, source)
+}
 
-        let mut cursor = tree.walk();
+/// Build chunks from a list of sibling statements and comments.
+fn build_chunks_from<'a>(children: &[Node<'a>], source: &[u8]) -> anyhow::Result<Vec<Chunk<'a>>> {
+    let mut chunks = Vec::<Chunk>::new();
 
-        let mut chunks = Vec::<Chunk>::new();
+    let mut comments = Vec::<Node>::new();
 
-        let mut comments = Vec::<Node>::new();
+    let mut prev_line: Option<usize> = None;
 
-        let mut prev_line: Option<usize> = None;
+    // `@class` blocks that weren't attached to anything, by index in `chunks`, with the
+    // line they end on
+    let mut unattached = Vec::<(usize, usize)>::new();
 
-        // parse files into chunks
-        // A chunk is a bunch of comments annotating some function or declaration.
-        // TODO: parse @alias
-        for child in cursor.node().children(&mut cursor) {
-            let start_line = child.range().start_point.row;
-            if child.kind() == "comment" {
-                if let Some(line) = prev_line {
-                    if start_line != line + 1 {
-                        comments.clear();
+    // TODO: parse @alias
+    for &child in children {
+        // Skip a leading `#!/usr/bin/env lua` so it doesn't take part in comment adjacency
+        if child.kind() == "hash_bang_line" {
+            continue;
+        }
+
+        let start_line = child.range().start_point.row;
+        if child.kind() == "comment" {
+            if let Some(line) = prev_line {
+                if start_line != line + 1 {
+                    if push_class_only(&comments, source, &mut chunks)? {
+                        unattached.push((chunks.len() - 1, line));
                     }
+                    comments.clear();
                 }
+            }
 
-                comments.push(child);
-                prev_line = Some(start_line);
-            } else if let Some(line) = prev_line {
-                if start_line == line + 1 {
-                    let (body, attributes) = parse_comments(&comments, contents.as_bytes())?;
+            comments.push(child);
+            // Block comments can span multiple lines, so adjacency is based on where they end.
+            // This way a commented out `--[[ ... ]]` chunk of code inside a doc block
+            // doesn't split it.
+            prev_line = Some(child.range().end_point.row);
+        } else if let Some(line) = prev_line {
+            if start_line == line + 1 {
+                let (body, attributes) = parse_comments(&comments, source)?;
+                if let Some(targets) = assignment_targets(child, source) {
+                    // `a, b = 1, 2` documents both `a` and `b`
+                    for decl in targets {
+                        chunks.push(Chunk {
+                            body: body.clone(),
+                            attributes: attributes.clone(),
+                            decl,
+                        });
+                    }
+                } else {
                     let mut cursor = child.walk();
-                    let decl = node_to_decl(child, &mut cursor, contents.as_bytes());
+                    let decl = node_to_decl(child, &mut cursor, source);
                     let chunk = Chunk {
                         body,
                         attributes,
                         decl,
                     };
                     chunks.push(chunk);
-                    comments.clear();
-                    prev_line = None;
                 }
-            } else {
-                comments.clear();
-                prev_line = None;
+            } else if push_class_only(&comments, source, &mut chunks)? {
+                unattached.push((chunks.len() - 1, line));
             }
+            // Either way the comments were used or aren't attached to anything
+            comments.clear();
+            prev_line = None;
+        } else {
+            comments.clear();
+            prev_line = None;
         }
+    }
 
-        let (mods_and_classes, rest): (Vec<_>, _) = chunks
-            .iter()
-            .filter(|chunk| {
-                !chunk
-                    .attributes
-                    .iter()
-                    .any(|attr| matches!(attr, Attribute::NoDoc))
-            })
-            .partition(|chunk| {
-                chunk
-                    .attributes
-                    .iter()
-                    .any(|attr| matches!(attr, Attribute::Class { .. }))
+    // A doc comment at the very end has nothing to document, but keep it so it isn't lost
+    if !comments.is_empty() {
+        let (body, attributes) = parse_comments(&comments, source)?;
+        if !body.is_empty() || !attributes.is_empty() {
+            chunks.push(Chunk {
+                body,
+                attributes,
+                decl: Declaration::Dangling,
             });
+        }
+    }
 
-        let mut methods = HashMap::<&str, Vec<&Chunk>>::new();
-        const NO_NAME: &str = "_NO_NAME";
-        methods.insert(NO_NAME, vec![]);
+    attach_classes(children, source, &mut chunks, &unattached);
 
-        for chunk in mods_and_classes.iter() {
-            let (Declaration::Function(Some(name), _) | Declaration::Variable(name, _)) =
-                &chunk.decl
-            else {
-                continue;
-            };
-            methods.insert(name, vec![]);
-        }
+    Ok(chunks)
+}
 
-        for chunk in rest.iter() {
-            if let Declaration::Function(Some(name), _) | Declaration::Variable(name, _) =
-                &chunk.decl
-            {
-                if let Some(v) = methods.get_mut(name.as_str()) {
-                    v.push(chunk);
-                } else {
-                    methods.get_mut(NO_NAME).unwrap().push(chunk);
-                }
-            } else {
-                methods.get_mut(NO_NAME).unwrap().push(chunk);
+/// Keep a doc block that isn't attached to any code if it declares a `@class`, like the
+/// type-only definitions in a stub file for the language server. Returns whether it was
+/// kept.
+fn push_class_only<'a>(
+    comments: &[Node],
+    source: &[u8],
+    chunks: &mut Vec<Chunk<'a>>,
+) -> anyhow::Result<bool> {
+    let (body, attributes) = parse_comments(comments, source)?;
+    if attributes
+        .iter()
+        .any(|attr| matches!(attr.value, Attribute::Class { .. }))
+    {
+        chunks.push(Chunk {
+            body,
+            attributes,
+            decl: Declaration::Dangling,
+        });
+        return Ok(true);
+    }
+    Ok(false)
+}
+
+/// Attach `@class` blocks written away from their table, like with other code in between,
+/// to the first later declaration of a variable with the class's name (or the last part of
+/// it, like `Foo` for `pkg.Foo`). `unattached` has the index in `chunks` of each block and
+/// the line it ends on.
+///
+/// A documented declaration gets the class's description and tags added to its own, and
+/// the block is removed. Blocks without a declaration stay as they are, as classes of
+/// their own.
+fn attach_classes<'a>(
+    children: &[Node<'a>],
+    source: &[u8],
+    chunks: &mut Vec<Chunk<'a>>,
+    unattached: &[(usize, usize)],
+) {
+    let matches = |name: &str, class: &str| {
+        name == class || class.rsplit_once('.').is_some_and(|(_, last)| last == name)
+    };
+
+    let mut merged = Vec::new();
+    for &(i, line) in unattached {
+        let Some(class) = chunks[i].class_name().map(str::to_string) else {
+            continue;
+        };
+
+        let documented = chunks.iter().position(|chunk| match &chunk.decl {
+            Declaration::Variable(name, node) => {
+                !chunk.is_class() && node.range().start_point.row > line && matches(name, &class)
             }
+            _ => false,
+        });
+        if let Some(j) = documented {
+            let block = &mut chunks[i];
+            let mut body = std::mem::take(&mut block.body);
+            let mut attributes = std::mem::take(&mut block.attributes);
+            let decl = &mut chunks[j];
+            if !body.is_empty() && !decl.body.is_empty() {
+                body.push(crate::attr::doc_prefix().to_string());
+            }
+            body.append(&mut decl.body);
+            attributes.append(&mut decl.attributes);
+            decl.body = body;
+            decl.attributes = attributes;
+            merged.push(i);
+            continue;
+        }
+
+        let mut cursor = children[0].walk();
+        let decl = children
+            .iter()
+            .filter(|child| child.range().start_point.row > line)
+            .filter(|child| {
+                matches!(
+                    child.kind(),
+                    "variable_declaration" | "assignment_statement"
+                )
+            })
+            .map(|&child| node_to_decl(child, &mut cursor, source))
+            .find(|decl| matches!(decl, Declaration::Variable(name, _) if matches(name, &class)));
+        if let Some(decl) = decl {
+            chunks[i].decl = decl;
+        }
+    }
+
+    // Later ones first so the earlier indices stay valid
+    merged.sort_unstable();
+    for i in merged.into_iter().rev() {
+        chunks.remove(i);
+    }
+}
+
+/// Build the chunks in the bodies of top-level functions for `--descend-bodies`, along
+/// with the declaration of the function each body belongs to.
+fn function_body_chunks<'a>(
+    root: Node<'a>,
+    source: &[u8],
+) -> anyhow::Result<Vec<(Declaration<'a>, Vec<Chunk<'a>>)>> {
+    let mut bodies = Vec::new();
+
+    let mut cursor = root.walk();
+    for child in root.children(&mut cursor) {
+        if child.kind() != "function_declaration" {
+            continue;
         }
+        let Some(body) = child.child_by_field_name("body") else {
+            continue;
+        };
+        // Comments before the first statement belong to the function, not its body
+        let mut cursor = child.walk();
+        let mut statements = child
+            .children(&mut cursor)
+            .filter(|node| node.kind() == "comment" && node.start_byte() < body.start_byte())
+            .filter(|node| {
+                child
+                    .child_by_field_name("parameters")
+                    .is_none_or(|params| node.start_byte() > params.end_byte())
+            })
+            .collect::<Vec<_>>();
+        let mut cursor = body.walk();
+        statements.extend(body.children(&mut cursor));
+
+        let mut decl_cursor = child.walk();
+        bodies.push((
+            node_to_decl(child, &mut decl_cursor, source),
+            build_chunks_from(&statements, source)?,
+        ));
+    }
 
-        let mut ldoc_text = String::new();
+    Ok(bodies)
+}
+
+/// Collect documented `self.<field> = ...` assignments in the bodies of top-level
+/// functions, keyed by the name of the table the function is defined on.
+fn collect_self_fields(bodies: &[(Declaration, Vec<Chunk>)]) -> HashMap<String, Vec<Attribute>> {
+    let mut fields = HashMap::<String, Vec<Attribute>>::new();
+
+    for (decl, chunks) in bodies {
+        let Declaration::Function(Some(class), _) = decl else {
+            continue;
+        };
 
-        // We have to place functions in a module/class in sections under the
-        // corresponding LDoc annotation. The loop below orders functions correctly
-        // as to not screw up LDoc generation.
-        for chunk in mods_and_classes {
-            let (Declaration::Function(Some(name), _) | Declaration::Variable(name, _)) =
-                &chunk.decl
-            else {
+        for chunk in chunks {
+            let Declaration::Variable(name, node) = &chunk.decl else {
+                continue;
+            };
+            let Some(field) = name.strip_prefix("self.") else {
                 continue;
             };
 
-            // println!("{}", chunk.to_ldoc_string(contents.as_bytes()));
-            ldoc_text.push_str(&chunk.to_ldoc_string(contents.as_bytes()));
-            // println!("{ldoc_text}");
-            if let Some(chunks) = methods.get(name.as_str()) {
-                for chunk in chunks.iter() {
-                    ldoc_text.push_str(&chunk.to_ldoc_string(contents.as_bytes()));
+            let value = node.child_by_field_name("value").or_else(|| {
+                let mut cursor = node.walk();
+                let exprs = node
+                    .children(&mut cursor)
+                    .find(|child| child.kind() == "expression_list");
+                exprs.and_then(|exprs| exprs.child_by_field_name("value"))
+            });
+
+            let desc = chunk
+                .body
+                .iter()
+                .map(|comment| comment.trim().trim_start_matches('-').trim())
+                .filter(|comment| !comment.is_empty())
+                .collect::<Vec<_>>()
+                .join(" ");
+
+            fields
+                .entry(class.clone())
+                .or_default()
+                .push(Attribute::Field {
+                    name: field.to_string(),
+                    ty: value.map(infer_literal_type).unwrap_or("any").to_string(),
+                    desc: (!desc.is_empty()).then_some(desc),
+                    visibility: Visibility::Public,
+                });
+        }
+    }
+
+    fields
+}
+
+/// Get `@field`s for every named key in the table constructor assigned by the
+/// variable declaration or assignment `node`, inferring their types from their values.
+///
+/// Doc comments directly above a key are used as its description.
+fn table_fields(node: Node, source: &[u8]) -> Vec<Attribute> {
+    let mut cursor = node.walk();
+    let asm_stmt = if node.kind() == "variable_declaration" {
+        let Some(asm_stmt) = node
+            .children(&mut cursor)
+            .find(|child| child.kind() == "assignment_statement")
+        else {
+            return Vec::new();
+        };
+        asm_stmt
+    } else {
+        node
+    };
+
+    let mut cursor = asm_stmt.walk();
+    let Some(table) = asm_stmt
+        .children(&mut cursor)
+        .find(|child| child.kind() == "expression_list")
+        .and_then(|exprs| exprs.child_by_field_name("value"))
+        .filter(|value| value.kind() == "table_constructor")
+    else {
+        return Vec::new();
+    };
+
+    let mut fields = Vec::new();
+    let mut desc = Vec::<&str>::new();
+
+    let mut cursor = table.walk();
+    for child in table.children(&mut cursor) {
+        match child.kind() {
+            "comment" => {
+                let text = child.utf8_text(source).unwrap_or_default();
+                if let Some(text) = text.strip_prefix(crate::attr::doc_prefix()) {
+                    desc.push(text.trim());
+                }
+            }
+            "field" => {
+                let name = child
+                    .child_by_field_name("name")
+                    .filter(|name| name.kind() == "identifier");
+                if let Some(name) = name {
+                    let desc = desc.join(" ");
+                    fields.push(Attribute::Field {
+                        name: name.utf8_text(source).unwrap_or_default().to_string(),
+                        ty: child
+                            .child_by_field_name("value")
+                            .map(infer_literal_type)
+                            .unwrap_or("any")
+                            .to_string(),
+                        desc: (!desc.is_empty()).then_some(desc),
+                        visibility: Visibility::Public,
+                    });
                 }
+                desc.clear();
             }
+            _ => (),
         }
+    }
+
+    fields
+}
+
+/// Infer an LDoc type from a literal expression node, falling back to `any`.
+fn infer_literal_type(node: Node) -> &'static str {
+    match node.kind() {
+        "number" => "number",
+        "string" => "string",
+        "true" | "false" => "boolean",
+        "table_constructor" => "table",
+        "function_definition" => "function",
+        _ => "any",
+    }
+}
 
-        for chunk in methods.get(NO_NAME).unwrap() {
-            ldoc_text.push_str(&chunk.to_ldoc_string(contents.as_bytes()));
+/// Parse comment blocks with [`attributes_from_comment_block`]. Each attribute keeps the
+/// 1-based line its tag is on.
+///
+/// `--[[-- ... ]]` block comments are split into lines first, so every tag in them is
+/// found. Other block comments are usually commented out code and stay whole, which
+/// leaves them out of the docs.
+fn parse_comments(
+    comments: &[Node],
+    source: &[u8],
+) -> anyhow::Result<(Vec<String>, Vec<Spanned<Attribute>>)> {
+    let mut lines = Vec::<(String, usize)>::new();
+    for comment in comments {
+        let text = comment.utf8_text(source)?;
+        let line = comment.range().start_point.row + 1;
+        match block_doc_lines(text) {
+            Some(block) => lines.extend(
+                block
+                    .into_iter()
+                    .map(|(offset, text)| (text, line + offset)),
+            ),
+            None => lines.push((text.to_string(), line)),
         }
+    }
+    let lines = lines
+        .iter()
+        .map(|(text, line)| (text.as_str(), *line))
+        .collect::<Vec<_>>();
+    Ok(attributes_from_comment_block(&lines))
+}
 
-        // TODO: also follow relative directory, not just file name
+/// The lines of an LDoc-style `--[[-- ... ]]` block comment as doc comment lines, with
+/// their common indentation removed. Each comes with how many lines into the comment it is.
+fn block_doc_lines(text: &str) -> Option<Vec<(usize, String)>> {
+    let rest = text.strip_prefix("--[")?;
+    let level = rest.len() - rest.trim_start_matches('=').len();
+    let rest = rest[level..].strip_prefix('[')?.strip_prefix("--")?;
+    let close = format!("]{}]", "=".repeat(level));
+    let rest = rest.trim_end();
+    let rest = rest.strip_suffix(close.as_str()).unwrap_or(rest);
 
-        crate::attr::replace_examples(&mut ldoc_text);
+    let mut lines = rest.lines();
+    // The first line can hold the summary, after any more dashes of a banner
+    let first = lines
+        .next()
+        .unwrap_or_default()
+        .trim_start_matches('-')
+        .trim();
+    let lines = lines.map(str::trim_end).collect::<Vec<_>>();
+    let indent = lines
+        .iter()
+        .filter(|line| !line.is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
 
-        crate::attr::replace_fences(&mut ldoc_text);
+    let prefix = crate::attr::doc_prefix();
+    let to_doc = |line: &str| match line {
+        "" => prefix.to_string(),
+        line if line.trim_start().starts_with(prefix) => line.trim_start().to_string(),
+        line => format!("{prefix} {line}"),
+    };
+    Some(
+        std::iter::once((0, first))
+            .filter(|(_, first)| !first.is_empty())
+            .map(|(offset, first)| (offset, to_doc(first)))
+            .chain(
+                lines
+                    .iter()
+                    .enumerate()
+                    .map(|(i, line)| (i + 1, to_doc(line.get(indent..).unwrap_or(line)))),
+            )
+            .collect(),
+    )
+}
 
-        std::fs::write(out_dir.join(entry.file_name()), ldoc_text)?;
+/// The name of an assignment target.
+///
+/// `T["key"]` is named `T.key` if `key` is a valid identifier and `T["some-key"]` otherwise.
+/// Computed keys like `T[key]` have no name.
+fn target_name(target: Node, source: &[u8]) -> Option<String> {
+    if target.kind() != "bracket_index_expression" {
+        return Some(target.utf8_text(source).expect("no name").to_string());
     }
 
-    Ok(())
+    let field = target.child_by_field_name("field")?;
+    if field.kind() != "string" {
+        return None;
+    }
+    let table = target
+        .child_by_field_name("table")?
+        .utf8_text(source)
+        .ok()?;
+    let key = field
+        .child_by_field_name("content")
+        .and_then(|content| content.utf8_text(source).ok())
+        .unwrap_or("");
+
+    if is_identifier(key) {
+        Some(format!("{table}.{key}"))
+    } else {
+        Some(format!("{table}[{}]", field.utf8_text(source).ok()?))
+    }
 }
 
-#[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
-struct Args {
-    #[arg(short, long, default_value_os_t = PathBuf::from("."))]
-    path: PathBuf,
-    #[arg(short, long, default_value_os_t = PathBuf::from("."))]
-    out_dir: PathBuf,
+/// Whether `name` is a valid Lua identifier.
+fn is_identifier(name: &str) -> bool {
+    const KEYWORDS: &[&str] = &[
+        "and", "break", "do", "else", "elseif", "end", "false", "for", "function", "goto", "if",
+        "in", "local", "nil", "not", "or", "repeat", "return", "then", "true", "until", "while",
+    ];
+
+    name.chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !KEYWORDS.contains(&name)
 }
 
-#[derive(Debug)]
-pub enum Declaration<'a> {
-    Function(Option<String>, Node<'a>),
-    Variable(String, Node<'a>),
-    Other(Node<'a>),
+/// The first target of an assignment or local declaration.
+pub fn first_target(node: Node) -> Option<Node> {
+    let mut cursor = node.walk();
+    let asm_stmt = match node.kind() {
+        "variable_declaration" => node
+            .children(&mut cursor)
+            .find(|child| child.kind() == "assignment_statement")
+            .unwrap_or(node),
+        _ => node,
+    };
+    let mut cursor = asm_stmt.walk();
+    let target = asm_stmt
+        .children(&mut cursor)
+        .find(|child| child.kind() == "variable_list")?
+        .child_by_field_name("name");
+    target
 }
 
-/// Parse comment blocks into two vectors: the first is a vector of summary/body comments
-/// as their nodes, and the second is a vector of attribute comments converted into
-/// [`Attribute`]s.
-fn parse_comments<'a>(
-    comments: &[Node<'a>],
-    source: &[u8],
-) -> anyhow::Result<(Vec<Node<'a>>, Vec<Attribute>)> {
-    // filter actual comments
-    let re = Regex::new(r"^[ \t]*---[ \t]*(@|\|)?").unwrap();
-    let comments = comments
-        .iter()
-        .filter(|comment| {
-            comment
-                .utf8_text(source)
-                .is_ok_and(|s| re.is_match(s.as_bytes()).is_ok_and(|ret| ret))
-        })
-        .collect::<Vec<_>>();
+/// The module `node` assigns with a `require("module")`, if its value is one.
+pub fn required_module(node: Node, source: &[u8]) -> Option<String> {
+    if !matches!(node.kind(), "variable_declaration" | "assignment_statement") {
+        return None;
+    }
+    let call = first_value(node).filter(|value| value.kind() == "function_call")?;
+    if call.child_by_field_name("name")?.utf8_text(source).ok()? != "require" {
+        return None;
+    }
+    // Both `require("module")` and `require "module"`
+    let args = call.child_by_field_name("arguments")?;
+    let arg = args.named_child(0).filter(|arg| arg.kind() == "string")?;
+    if args.named_child_count() != 1 {
+        return None;
+    }
+    Some(
+        arg.child_by_field_name("content")?
+            .utf8_text(source)
+            .ok()?
+            .to_string(),
+    )
+}
 
-    let mut body = Vec::<Node>::new();
-    let mut attributes = Vec::<Attribute>::new();
-    for comment in comments {
-        let text = comment.utf8_text(source)?; // TODO: not ?, continue
-        let attr = if let Ok(Some(captures)) = ATTR_REGEXES.param.captures(text.as_bytes()) {
-            (|| {
-                Some(Attribute::Param {
-                    name: std::str::from_utf8(captures.name("name")?.as_bytes())
-                        .ok()?
-                        .to_string(),
-                    ty: std::str::from_utf8(captures.name("ty")?.as_bytes())
-                        .ok()?
-                        .to_string(),
-                    desc: captures.name("desc").and_then(|desc| {
-                        Some(std::str::from_utf8(desc.as_bytes()).ok()?.to_string())
-                    }),
-                })
-            })()
-        } else if let Ok(Some(captures)) = ATTR_REGEXES.ret.captures(text.as_bytes()) {
-            (|| {
-                Some(Attribute::Return {
-                    ty: std::str::from_utf8(captures.name("ty")?.as_bytes())
-                        .ok()?
-                        .to_string(),
-                    name: captures.name("name").and_then(|desc| {
-                        Some(std::str::from_utf8(desc.as_bytes()).ok()?.to_string())
-                    }),
-                    desc: captures.name("desc").and_then(|desc| {
-                        Some(std::str::from_utf8(desc.as_bytes()).ok()?.to_string())
-                    }),
-                })
-            })()
-        } else if let Ok(Some(captures)) = ATTR_REGEXES.see.captures(text.as_bytes()) {
-            (|| {
-                Some(Attribute::See {
-                    link: std::str::from_utf8(captures.name("link")?.as_bytes())
-                        .ok()?
-                        .to_string(),
-                    desc: captures.name("desc").and_then(|desc| {
-                        Some(std::str::from_utf8(desc.as_bytes()).ok()?.to_string())
-                    }),
-                })
-            })()
-        } else if let Ok(Some(captures)) = ATTR_REGEXES.class.captures(text.as_bytes()) {
-            (|| {
-                Some(Attribute::Class {
-                    ty: std::str::from_utf8(captures.name("ty")?.as_bytes())
-                        .ok()?
-                        .to_string(),
-                })
-            })()
-        } else if let Ok(true) = ATTR_REGEXES.classmod.is_match(text.as_bytes()) {
-            Some(Attribute::ClassMod)
-        } else if ATTR_REGEXES.nodoc.is_match(text) {
-            Some(Attribute::NoDoc)
-        } else if let Ok(true) = ATTR_REGEXES.alias.is_match(text.as_bytes()) {
-            panic!("@aliases weren't processed before parsing comments");
-        } else {
-            body.push(*comment);
-            None
-        };
+/// The first value assigned by an assignment or local declaration.
+pub fn first_value(node: Node) -> Option<Node> {
+    let mut cursor = node.walk();
+    let asm_stmt = match node.kind() {
+        "variable_declaration" => node
+            .children(&mut cursor)
+            .find(|child| child.kind() == "assignment_statement")?,
+        _ => node,
+    };
+    let mut cursor = asm_stmt.walk();
+    let value = asm_stmt
+        .children(&mut cursor)
+        .find(|child| child.kind() == "expression_list")?
+        .child_by_field_name("value");
+    value
+}
 
-        if let Some(attr) = attr {
-            attributes.push(attr);
-        }
+/// If `node` is an assignment with more than one target, return a [`Declaration::Target`]
+/// for each one.
+fn assignment_targets<'a>(node: Node<'a>, source: &[u8]) -> Option<Vec<Declaration<'a>>> {
+    let mut cursor = node.walk();
+    let asm_stmt = match node.kind() {
+        "variable_declaration" => node
+            .children(&mut cursor)
+            .find(|child| child.kind() == "assignment_statement")
+            .unwrap_or(node),
+        "assignment_statement" => node,
+        _ => return None,
+    };
+
+    let mut cursor = asm_stmt.walk();
+    let var_list = asm_stmt
+        .children(&mut cursor)
+        .find(|child| child.kind() == "variable_list")?;
+    let names = var_list
+        .children_by_field_name("name", &mut cursor)
+        .collect::<Vec<_>>();
+    if names.len() < 2 {
+        return None;
     }
-    Ok((body, attributes))
+
+    let values = asm_stmt
+        .children(&mut cursor)
+        .find(|child| child.kind() == "expression_list")
+        .map(|exprs| {
+            exprs
+                .children_by_field_name("value", &mut exprs.walk())
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    let targets = names
+        .into_iter()
+        .enumerate()
+        .map(|(i, name)| {
+            Declaration::Target(
+                name.utf8_text(source).expect("no name").to_string(),
+                name,
+                values.get(i).copied(),
+            )
+        })
+        .collect();
+
+    Some(targets)
 }
 
 fn node_to_decl<'a>(node: Node<'a>, cursor: &mut TreeCursor<'a>, source: &[u8]) -> Declaration<'a> {
@@ -302,20 +2770,22 @@ fn node_to_decl<'a>(node: Node<'a>, cursor: &mut TreeCursor<'a>, source: &[u8])
                     .children(cursor)
                     .find(|child| child.kind() == "variable_list")
                     .and_then(|var_list| var_list.child_by_field_name("name"))
-                    .expect("var decl had no name")
-                    .utf8_text(source)
-                    .expect("no name");
-                Declaration::Variable(name.to_string(), node)
+                    .expect("var decl had no name");
+                match target_name(name, source) {
+                    Some(name) => Declaration::Variable(name, node),
+                    None => Declaration::Other(node),
+                }
             } else if let Some(var_list) = node
                 .children(cursor)
                 .find(|child| child.kind() == "variable_list")
             {
                 let name = var_list
                     .child_by_field_name("name")
-                    .expect("var decl had no name")
-                    .utf8_text(source)
-                    .expect("no name");
-                Declaration::Variable(name.to_string(), node)
+                    .expect("var decl had no name");
+                match target_name(name, source) {
+                    Some(name) => Declaration::Variable(name, node),
+                    None => Declaration::Other(node),
+                }
             } else {
                 Declaration::Other(node)
             }
@@ -328,10 +2798,11 @@ fn node_to_decl<'a>(node: Node<'a>, cursor: &mut TreeCursor<'a>, source: &[u8])
             {
                 let name = var_list
                     .child_by_field_name("name")
-                    .expect("var decl had no name")
-                    .utf8_text(source)
-                    .expect("no name");
-                Declaration::Variable(name.to_string(), node)
+                    .expect("var decl had no name");
+                match target_name(name, source) {
+                    Some(name) => Declaration::Variable(name, node),
+                    None => Declaration::Other(node),
+                }
             } else {
                 Declaration::Other(node)
             }
@@ -351,7 +2822,8 @@ fn node_to_decl<'a>(node: Node<'a>, cursor: &mut TreeCursor<'a>, source: &[u8])
                         let name = name.utf8_text(source).expect("no name");
                         Declaration::Function(Some(name.to_string()), node)
                     }
-                    _ => panic!("name isn't index expression or identifier"),
+                    // Can happen with --best-effort on files with syntax errors
+                    _ => Declaration::Other(node),
                 }
             } else {
                 Declaration::Other(node)
@@ -360,3 +2832,103 @@ fn node_to_decl<'a>(node: Node<'a>, cursor: &mut TreeCursor<'a>, source: &[u8])
         _ => Declaration::Other(node),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dotted_module_names() {
+        assert_eq!(dotted_module_name(Path::new("pkg/foo.lua")), "pkg.foo");
+        assert_eq!(dotted_module_name(Path::new("pkg/foo/init.lua")), "pkg.foo");
+        assert_eq!(
+            dotted_module_name(Path::new("./lua/pkg/foo.lua")),
+            "pkg.foo"
+        );
+        assert_eq!(dotted_module_name(Path::new("init.lua")), "init");
+        assert_eq!(dotted_module_name(Path::new("lua.lua")), "lua");
+    }
+
+    fn lua_parser() -> tree_sitter::Parser {
+        let mut parser = tree_sitter::Parser::new();
+        parser.set_language(tree_sitter_lua::language()).unwrap();
+        parser
+    }
+
+    /// `tree` reparsed for `new` after the edit from `old`.
+    fn reparse(parser: &mut tree_sitter::Parser, old: &str, tree: &Tree, new: &str) -> Tree {
+        let mut tree = tree.clone();
+        tree.edit(&source_edit(old, new));
+        parser.parse(new, Some(&tree)).unwrap()
+    }
+
+    #[test]
+    fn incremental_reparses_match_full_ones() {
+        let mut parser = lua_parser();
+        let old = "---Does ä.\nfunction M.a(x) end\n\nlocal b = 1\n";
+        let tree = parser.parse(old, None).unwrap();
+        for new in [
+            old,
+            "---Does ä.\nfunction M.a(x, y) end\n\nlocal b = 1\n",
+            "---Does ö now.\nfunction M.a(x) end\n\nlocal b = 1\n",
+            "local c = 2\n---Does ä.\nfunction M.a(x) end\n\nlocal b = 1\n",
+            "---Does ä.\nfunction M.a(x) end\n",
+            "",
+        ] {
+            let full = parser.parse(new, None).unwrap();
+            assert_eq!(
+                reparse(&mut parser, old, &tree, new).root_node().to_sexp(),
+                full.root_node().to_sexp(),
+                "{new:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn edits_cover_only_what_changed() {
+        let edit = source_edit("a\nbcd\ne", "a\nbXd\ne");
+        assert_eq!(
+            (edit.start_byte, edit.old_end_byte, edit.new_end_byte),
+            (3, 4, 4)
+        );
+        assert_eq!(edit.start_position, Point { row: 1, column: 1 });
+        // The repeated `b` could be either one, it only has to be counted once
+        let edit = source_edit("ab", "abb");
+        assert_eq!(
+            (edit.start_byte, edit.old_end_byte, edit.new_end_byte),
+            (2, 2, 3)
+        );
+    }
+
+    /// Run with `cargo test --release -- --ignored --nocapture reparse_benchmark`.
+    #[test]
+    #[ignore]
+    fn reparse_benchmark() {
+        let mut old = String::new();
+        for i in 0..5000 {
+            old.push_str(&format!(
+                "---Function {i}.\n---@param x number\nfunction M.f{i}(x)\n    return x + {i}\nend\n\n"
+            ));
+        }
+        // A change in the middle of the file, like a save after editing one function
+        let new = old.replacen("return x + 2500", "return x * 2500", 1);
+        let mut parser = lua_parser();
+        let tree = parser.parse(&old, None).unwrap();
+
+        let runs = 20;
+        let start = std::time::Instant::now();
+        for _ in 0..runs {
+            parser.parse(&new, None).unwrap();
+        }
+        let full = start.elapsed() / runs;
+        let start = std::time::Instant::now();
+        for _ in 0..runs {
+            reparse(&mut parser, &old, &tree, &new);
+        }
+        let incremental = start.elapsed() / runs;
+        println!(
+            "{} lines: full parse {full:?}, incremental {incremental:?}",
+            new.lines().count()
+        );
+    }
+}
@@ -0,0 +1,250 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Rendering chunks as Markdown for `--format markdown`.
+
+use crate::{
+    attr::{is_url, module_path, return_description, usage_code, Attribute, Visibility},
+    chunk::Chunk,
+    config::rename_types,
+    strings::{self, Text},
+    Args, Declaration,
+};
+
+impl Chunk<'_> {
+    pub fn to_markdown(&self, source: &[u8], class: Option<&str>, args: &Args) -> String {
+        let mut ret = String::new();
+        ret.push('\n');
+
+        match self.class_name() {
+            Some(class) => ret.push_str(&format!("## {}\n\n", module_path(class))),
+            None if matches!(self.decl, Declaration::Dangling) => (),
+            None => ret.push_str(&format!("### `{}`\n\n", self.signature(source, args))),
+        }
+
+        let body = self
+            .normalized_body()
+            .iter()
+            .map(|line| {
+                let line = line.strip_prefix("---").unwrap_or(line);
+                line.strip_prefix(' ').unwrap_or(line).to_string()
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        if !body.trim().is_empty() {
+            ret.push_str(body.trim());
+            ret.push_str("\n\n");
+        }
+
+        if let Some(generics) = self.generics() {
+            ret.push_str(&format!("{generics}\n\n"));
+        }
+
+        if self
+            .attrs()
+            .any(|attr| matches!(attr, Attribute::Nodiscard))
+        {
+            ret.push_str(&format!("{}\n\n", strings::get(Text::Nodiscard)));
+        }
+
+        if let Some(note) = self.deprecation_note() {
+            ret.push_str(&format!("{note}\n\n"));
+        }
+
+        if let Some(op) = self.metamethod(source) {
+            ret.push_str(&format!("{} {op}.\n\n", strings::get(Text::Metamethod)));
+        }
+
+        for attr in self.attrs() {
+            if let Attribute::Source { source } = attr {
+                ret.push_str(&format!("{}: `{source}`\n\n", strings::get(Text::Source)));
+            }
+        }
+
+        let is_method = matches!(self.decl, Declaration::Function(..))
+            && self.decl_text(source, args).contains(':');
+        let mut params = self
+            .attrs()
+            .filter_map(|attr| match attr {
+                Attribute::Param { name, ty, desc } => Some((name, ty, desc.as_deref())),
+                _ => None,
+            })
+            .filter(|(name, ..)| !(is_method && *name == "self"))
+            .map(|(name, ty, desc)| (name.clone(), ty.clone(), desc))
+            .collect::<Vec<_>>();
+        if let Some(fun_params) = self.fun_type() {
+            // Matched by occurrence rather than just name, so repeated `_`s all show up
+            let mut documented = params
+                .iter()
+                .map(|(name, ..)| name.clone())
+                .collect::<Vec<_>>();
+            for (name, ty) in fun_params {
+                match documented.iter().position(|param| param == name) {
+                    Some(i) => {
+                        documented.remove(i);
+                    }
+                    None => params.push((name.clone(), ty.clone(), None)),
+                }
+            }
+        }
+        if !params.is_empty() {
+            ret.push_str("**Parameters**\n\n| Name | Type | Description |\n| --- | --- | --- |\n");
+            for (name, ty, desc) in params {
+                ret.push_str(&table_row(&name, &ty, desc));
+            }
+            ret.push('\n');
+        }
+
+        let mut returns = self
+            .attrs()
+            .filter_map(|attr| match attr {
+                Attribute::Return { ty, name, desc } => Some((
+                    match name.as_deref() {
+                        Some("...") => format!("{}...", self.return_type(ty, class)),
+                        _ => self.return_type(ty, class),
+                    },
+                    return_description(name.as_deref(), desc.as_deref()),
+                )),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        if returns.is_empty() {
+            for attr in self.attrs() {
+                if let Attribute::FunType { returns: tys, .. } = attr {
+                    returns.extend(tys.iter().map(|ty| (ty.clone(), None)));
+                }
+            }
+        }
+        if !returns.is_empty() {
+            ret.push_str("**Returns**\n\n");
+            for (ty, desc) in returns {
+                ret.push_str(&return_item(&ty, desc));
+            }
+            ret.push('\n');
+        }
+
+        let raises = self
+            .attrs()
+            .filter_map(|attr| match attr {
+                Attribute::Raise { desc } => Some(desc),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        if !raises.is_empty() {
+            ret.push_str("**Raises**\n\n");
+            for desc in raises {
+                ret.push_str(&format!("- {desc}\n"));
+            }
+            ret.push('\n');
+        }
+
+        let fields = self
+            .attrs()
+            .filter_map(|attr| match attr {
+                Attribute::Field {
+                    name,
+                    ty,
+                    desc,
+                    visibility,
+                } if *visibility == Visibility::Public || args.include_private => {
+                    Some((name, ty, desc.as_deref()))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        if !fields.is_empty() {
+            ret.push_str("**Fields**\n\n| Name | Type | Description |\n| --- | --- | --- |\n");
+            for (name, ty, desc) in fields {
+                ret.push_str(&table_row(name, ty, desc));
+            }
+            ret.push('\n');
+        }
+
+        for attr in self.attrs() {
+            if let Attribute::Usage { lines } = attr {
+                if self.usage_in_examples(attr, args) {
+                    continue;
+                }
+                ret.push_str(&usage_block(lines));
+            }
+        }
+
+        let see = self
+            .attrs()
+            .filter_map(|attr| match attr {
+                Attribute::See { link, desc } if is_url(link) => {
+                    Some(format!("[{}]({link})", desc.as_deref().unwrap_or(link)))
+                }
+                Attribute::See { link, .. } => Some(format!("`{link}`")),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        if !see.is_empty() {
+            ret.push_str(&format!("**See also:** {}\n\n", see.join(", ")));
+        }
+
+        for attr in self.attrs() {
+            match attr {
+                Attribute::Since { version } => ret.push_str(&format!("**Since:** {version}\n\n")),
+                Attribute::Version { version } => {
+                    ret.push_str(&format!("**Version:** {version}\n\n"))
+                }
+                _ => (),
+            }
+        }
+
+        ret
+    }
+
+    /// How the declaration is shown in its heading, like `Foo:bar(a, b)`.
+    fn signature(&self, source: &[u8], args: &Args) -> String {
+        match &self.decl {
+            Declaration::Variable(name, _) | Declaration::Target(name, ..)
+                if self.fun_type().is_none() =>
+            {
+                name.clone()
+            }
+            _ => {
+                let decl = self.decl_text(source, args);
+                let decl = decl.lines().next().unwrap_or_default().trim();
+                let decl = decl.strip_prefix("local ").unwrap_or(decl);
+                let decl = decl.strip_prefix("function ").unwrap_or(decl);
+                decl.strip_suffix("end").unwrap_or(decl).trim().to_string()
+            }
+        }
+    }
+}
+
+/// A row of a parameter or field table.
+pub fn table_row(name: &str, ty: &str, desc: Option<&str>) -> String {
+    format!(
+        "| `{name}` | `{}` | {} |\n",
+        format_type(ty).replace('|', "\\|"),
+        desc.unwrap_or_default().replace('|', "\\|")
+    )
+}
+
+/// An item in the list of returns.
+pub fn return_item(ty: &str, desc: Option<String>) -> String {
+    match desc {
+        Some(desc) => format!("- `{}` {desc}\n", format_type(ty)),
+        None => format!("- `{}`\n", format_type(ty)),
+    }
+}
+
+/// A `@usage` as a Lua code block.
+pub fn usage_block(lines: &[String]) -> String {
+    let code = usage_code(lines)
+        .lines()
+        .map(|line| line.strip_prefix("---").unwrap_or(line))
+        .map(|line| line.strip_prefix(' ').unwrap_or(line))
+        .collect::<Vec<_>>()
+        .join("\n");
+    format!("**Usage**\n\n```lua\n{code}\n```\n\n")
+}
+
+/// LuaLS types are readable enough as-is, they just need a consistent `nil`.
+fn format_type(ty: &str) -> String {
+    rename_types(&ty.trim().replace('?', "|nil"))
+}
@@ -0,0 +1,89 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::{
+    io::IsTerminal,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use indicatif::{ProgressBar, ProgressStyle};
+
+/// The fewest files between log lines, so small runs don't log every file
+const MIN_LOG_STEP: usize = 10;
+
+/// Reports how many files have been processed. Safe to update from worker threads.
+pub enum Progress {
+    /// A progress bar, used when stderr is a terminal
+    Bar(ProgressBar),
+    /// Periodic log lines, used when stderr isn't a terminal (e.g. in CI)
+    Log {
+        total: usize,
+        done: AtomicUsize,
+    },
+    Hidden,
+}
+
+impl Progress {
+    pub fn new(total: usize, enabled: bool) -> Self {
+        if !enabled {
+            Progress::Hidden
+        } else if std::io::stderr().is_terminal() {
+            let bar = ProgressBar::new(total as u64);
+            if let Ok(style) = ProgressStyle::with_template("{bar:40} {pos}/{len} files") {
+                bar.set_style(style);
+            }
+            Progress::Bar(bar)
+        } else {
+            Progress::Log {
+                total,
+                done: AtomicUsize::new(0),
+            }
+        }
+    }
+
+    /// Mark one more file as processed.
+    pub fn inc(&self) {
+        match self {
+            Progress::Bar(bar) => bar.inc(1),
+            Progress::Log { total, done } => {
+                let done = done.fetch_add(1, Ordering::Relaxed) + 1;
+                if should_log(done, *total) {
+                    eprintln!("Processed {done}/{total} files");
+                }
+            }
+            Progress::Hidden => (),
+        }
+    }
+
+    pub fn finish(&self) {
+        if let Progress::Bar(bar) = self {
+            bar.finish_and_clear();
+        }
+    }
+}
+
+/// Whether to log after `done` of `total` files: roughly every 10%, but at most every
+/// [`MIN_LOG_STEP`] files, and at the end.
+fn should_log(done: usize, total: usize) -> bool {
+    let step = (total / 10).max(MIN_LOG_STEP);
+    done.is_multiple_of(step) || done == total
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn logged(total: usize) -> Vec<usize> {
+        (1..=total)
+            .filter(|&done| should_log(done, total))
+            .collect()
+    }
+
+    #[test]
+    fn small_runs_log_rarely() {
+        assert_eq!(logged(3), [3]);
+        assert_eq!(logged(15), [10, 15]);
+        assert_eq!(logged(200).len(), 10);
+    }
+}
@@ -0,0 +1,100 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Turning editor folding markers like `--#region Name` into LDoc `@within` sections for
+//! `--regions-as-sections`.
+
+use std::ops::Range;
+
+use crate::{attr::Attribute, chunk::Chunk, diag, Declaration, SourceFile};
+
+/// A line that starts or ends a region.
+enum Marker {
+    Start(String),
+    End,
+}
+
+/// Put each chunk declared between `--#region Name` and `--#endregion` in a `@within Name`
+/// section, unless it already has one. With nested regions the innermost one wins.
+///
+/// Regions that aren't closed are warned about and last until the end of the file.
+pub fn add_region_sections(file: &SourceFile, chunks: &mut [Chunk]) {
+    let source = file.contents.as_bytes();
+    let root = file.tree.root_node();
+
+    let mut open = Vec::<(String, usize)>::new();
+    let mut regions = Vec::<(String, Range<usize>)>::new();
+    let mut cursor = root.walk();
+    for child in root.children(&mut cursor) {
+        if child.kind() != "comment" {
+            continue;
+        }
+        let row = child.range().start_point.row;
+        match child.utf8_text(source).ok().and_then(marker) {
+            Some(Marker::Start(name)) => open.push((name, row)),
+            Some(Marker::End) => {
+                // A stray `--#endregion` doesn't close anything
+                if let Some((name, start)) = open.pop() {
+                    regions.push((name, start..row));
+                }
+            }
+            None => (),
+        }
+    }
+    for (name, start) in open {
+        diag::warn(
+            &file.path,
+            Some(start + 1),
+            diag::Kind::UnclosedRegion,
+            &format!("region `{name}` is never closed, so it lasts until the end of the file"),
+        );
+        regions.push((name, start..usize::MAX));
+    }
+
+    for chunk in chunks.iter_mut() {
+        // Modules and classes aren't in a section of anything
+        if chunk.is_class() || chunk.section().is_some() {
+            continue;
+        }
+        let row = match &chunk.decl {
+            Declaration::Function(_, node)
+            | Declaration::Variable(_, node)
+            | Declaration::Target(_, node, _)
+            | Declaration::Other(node) => node.range().start_point.row,
+            Declaration::Dangling => continue,
+        };
+        let region = regions
+            .iter()
+            .filter(|(name, lines)| !name.is_empty() && lines.contains(&row))
+            .max_by_key(|(_, lines)| lines.start);
+        if let Some((name, _)) = region {
+            chunk.attributes.push(
+                Attribute::Within {
+                    section: name.clone(),
+                }
+                .into(),
+            );
+        }
+    }
+}
+
+/// The region marker in a comment like `--#region Name` or `-- #endregion`, if it is one.
+/// Regions without a name are still matched up with their `--#endregion`, but don't make
+/// a section.
+fn marker(comment: &str) -> Option<Marker> {
+    let text = comment
+        .strip_prefix("--")?
+        .trim_start_matches('-')
+        .trim_start();
+    if let Some(rest) = text.strip_prefix("#endregion") {
+        if rest.is_empty() || rest.starts_with(char::is_whitespace) {
+            return Some(Marker::End);
+        }
+    }
+    let rest = text.strip_prefix("#region")?;
+    if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    Some(Marker::Start(rest.trim().to_string()))
+}
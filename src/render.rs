@@ -0,0 +1,70 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Output formats. Parsing doesn't know about any of them, it just produces chunks for
+//! a [`Renderer`] to turn into text.
+
+use crate::{
+    attr::{return_description, Attribute},
+    chunk::Chunk,
+    markdown, Args, OutputFormat,
+};
+
+/// Turns parsed chunks into the text of an output format.
+pub trait Renderer {
+    /// Render a chunk, including its description and all of its attributes. `class` is the
+    /// class the chunk is grouped under, if any, for linking back to it.
+    fn render_chunk(&self, chunk: &Chunk, class: Option<&str>, source: &[u8]) -> String;
+
+    /// Render a single attribute on its own. Attributes that don't show up in this format
+    /// render as an empty string.
+    fn render_attribute(&self, attr: &Attribute) -> String;
+}
+
+/// LDoc-annotated Lua, the default.
+pub struct LdocRenderer<'a> {
+    pub args: &'a Args,
+}
+
+impl Renderer for LdocRenderer<'_> {
+    fn render_chunk(&self, chunk: &Chunk, class: Option<&str>, source: &[u8]) -> String {
+        chunk.to_ldoc_string(source, class, self, self.args)
+    }
+
+    fn render_attribute(&self, attr: &Attribute) -> String {
+        attr.to_ldoc_string()
+    }
+}
+
+/// Markdown API reference pages for `--format markdown`.
+pub struct MarkdownRenderer<'a> {
+    pub args: &'a Args,
+}
+
+impl Renderer for MarkdownRenderer<'_> {
+    fn render_chunk(&self, chunk: &Chunk, class: Option<&str>, source: &[u8]) -> String {
+        chunk.to_markdown(source, class, self.args)
+    }
+
+    fn render_attribute(&self, attr: &Attribute) -> String {
+        match attr {
+            Attribute::Param { name, ty, desc } | Attribute::Field { name, ty, desc, .. } => {
+                markdown::table_row(name, ty, desc.as_deref())
+            }
+            Attribute::Return { ty, name, desc } => {
+                markdown::return_item(ty, return_description(name.as_deref(), desc.as_deref()))
+            }
+            Attribute::Usage { lines } => markdown::usage_block(lines),
+            _ => String::new(),
+        }
+    }
+}
+
+/// The renderer for `--format`.
+pub fn renderer(args: &Args) -> Box<dyn Renderer + '_> {
+    match args.format {
+        OutputFormat::Ldoc => Box::new(LdocRenderer { args }),
+        OutputFormat::Markdown => Box::new(MarkdownRenderer { args }),
+    }
+}
@@ -0,0 +1,92 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Timing of each phase of a run for `--stats`.
+
+use std::{
+    sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    time::{Duration, Instant},
+};
+
+/// A part of the run that's timed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// Finding the files to convert
+    Walk,
+    /// Reading files from disk
+    Read,
+    /// Parsing with tree-sitter
+    Parse,
+    /// Matching attributes, aliases, and examples with regexes, along with building the
+    /// chunks they're in
+    Regex,
+    /// Turning chunks into output text
+    Render,
+    /// Writing output files
+    Write,
+}
+
+impl Phase {
+    const ALL: [Phase; 6] = [
+        Phase::Walk,
+        Phase::Read,
+        Phase::Parse,
+        Phase::Regex,
+        Phase::Render,
+        Phase::Write,
+    ];
+
+    fn name(&self) -> &'static str {
+        match self {
+            Phase::Walk => "walk",
+            Phase::Read => "read",
+            Phase::Parse => "parse",
+            Phase::Regex => "regex",
+            Phase::Render => "render",
+            Phase::Write => "write",
+        }
+    }
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+/// Nanoseconds spent in each phase, indexed like [`Phase::ALL`]
+static NANOS: [AtomicU64; 6] = [const { AtomicU64::new(0) }; 6];
+
+/// Start timing phases. Until this is called, [`time`] doesn't measure anything.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Run `f`, adding how long it took to `phase`. Safe to call from worker threads.
+pub fn time<T>(phase: Phase, f: impl FnOnce() -> T) -> T {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return f();
+    }
+
+    let start = Instant::now();
+    let ret = f();
+    NANOS[phase as usize].fetch_add(start.elapsed().as_nanos() as u64, Ordering::Relaxed);
+    ret
+}
+
+/// Print how long each phase took. Phases run on worker threads are summed across
+/// threads, so they can add up to more than the wall time.
+pub fn print() {
+    let total = NANOS
+        .iter()
+        .map(|nanos| nanos.load(Ordering::Relaxed))
+        .sum::<u64>()
+        .max(1);
+
+    println!("{:8}  {:>10}  {:>6}", "phase", "time", "share");
+    for phase in Phase::ALL {
+        let nanos = NANOS[phase as usize].load(Ordering::Relaxed);
+        println!(
+            "{:8}  {:>10.2?}  {:>5.1}%",
+            phase.name(),
+            Duration::from_nanos(nanos),
+            nanos as f64 / total as f64 * 100.0
+        );
+    }
+}
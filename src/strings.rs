@@ -0,0 +1,115 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Text that gets added to the generated docs, overridable with `--lang`.
+
+use std::{collections::HashMap, path::Path, sync::OnceLock};
+
+use anyhow::Context;
+
+/// A piece of text added to the output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Text {
+    /// Added to the description of `@nodiscard` functions
+    Nodiscard,
+    /// Comes before the message of a `@deprecated`
+    Deprecated,
+    /// Comes before the value of a `@source`
+    Source,
+    /// Comes before the operator a metamethod like `__add` implements
+    Metamethod,
+    /// The title of the `--emit-index` page
+    Index,
+    /// Comes before the type parameters of a generic class
+    TypeParams,
+    /// Comes before the source path in `--emit-source-comment` comments
+    GeneratedFrom,
+    /// Comes before a link from a `@see` with a URL
+    SeeAlso,
+    /// Comes before the parent class of a field listed by `--show-inherited`
+    InheritedFrom,
+}
+
+impl Text {
+    const ALL: &'static [Text] = &[
+        Text::Nodiscard,
+        Text::Deprecated,
+        Text::Source,
+        Text::Metamethod,
+        Text::Index,
+        Text::TypeParams,
+        Text::GeneratedFrom,
+        Text::SeeAlso,
+        Text::InheritedFrom,
+    ];
+
+    /// The key used in the `--lang` file.
+    fn key(&self) -> &'static str {
+        match self {
+            Text::Nodiscard => "nodiscard",
+            Text::Deprecated => "deprecated",
+            Text::Source => "source",
+            Text::Metamethod => "metamethod",
+            Text::Index => "index",
+            Text::TypeParams => "type_params",
+            Text::GeneratedFrom => "generated_from",
+            Text::SeeAlso => "see_also",
+            Text::InheritedFrom => "inherited_from",
+        }
+    }
+
+    fn default_text(&self) -> &'static str {
+        match self {
+            Text::Nodiscard => "The return value should be used.",
+            Text::Deprecated => "Deprecated",
+            Text::Source => "Source",
+            Text::Metamethod => "Metamethod for",
+            Text::Index => "Modules",
+            Text::TypeParams => "Type parameters",
+            Text::GeneratedFrom => "Generated from",
+            Text::SeeAlso => "See also",
+            Text::InheritedFrom => "Inherited from",
+        }
+    }
+}
+
+static STRINGS: OnceLock<HashMap<Text, String>> = OnceLock::new();
+
+/// Load overrides from the TOML file at `path`, a flat table of keys to text.
+/// This should be called once at startup.
+pub fn init(path: Option<&Path>) -> anyhow::Result<()> {
+    let Some(path) = path else {
+        return Ok(());
+    };
+
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("couldn't read {}", path.display()))?;
+    let overrides = toml::from_str::<HashMap<String, String>>(&contents)
+        .with_context(|| format!("couldn't parse {}", path.display()))?;
+
+    let mut strings = HashMap::new();
+    for (key, text) in overrides {
+        let Some(which) = Text::ALL.iter().find(|which| which.key() == key) else {
+            let keys = Text::ALL.iter().map(Text::key).collect::<Vec<_>>();
+            anyhow::bail!(
+                "{}: unknown key `{key}`, expected one of {}",
+                path.display(),
+                keys.join(", ")
+            );
+        };
+        strings.insert(*which, text);
+    }
+
+    let _ = STRINGS.set(strings);
+    Ok(())
+}
+
+/// Get the text for `which`, using the override if there is one.
+pub fn get(which: Text) -> &'static str {
+    STRINGS
+        .get()
+        .and_then(|strings| strings.get(&which))
+        .map(String::as_str)
+        .unwrap_or(which.default_text())
+}
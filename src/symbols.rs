@@ -0,0 +1,412 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::LazyLock,
+};
+
+use crate::{
+    attr::{is_url, module_path, Attribute},
+    chunk::Chunk,
+    diag, dotted_module_name, required_module, returned_name, Declaration, SourceFile,
+};
+
+/// The name of a generic parameter, like `T` in `T : Foo`.
+fn generic_name(generic: &str) -> &str {
+    generic.split(':').next().unwrap_or_default().trim()
+}
+
+/// A name in backticks in a description, like `` `Foo` ``, `` `Foo.bar` ``, or
+/// `` `Foo:baz()` ``.
+static INLINE_REF_REGEX: LazyLock<regex::Regex> = LazyLock::new(|| {
+    regex::Regex::new(r"`(?<ref>[A-Za-z_]\w*(?:[.:][A-Za-z_]\w*)*)(?:\(\))?`").unwrap()
+});
+
+/// A name in a type, like `Foo` in `Foo[]|nil`. Strings are matched so names in them can be
+/// skipped, and so are names followed by `:`, which are keys or parameter names like the `x`
+/// in `fun(x: Foo)`.
+static TYPE_NAME_REGEX: LazyLock<regex::Regex> = LazyLock::new(|| {
+    regex::Regex::new(r#""[^"]*"|'[^']*'|(?<name>[A-Za-z_][\w.]*)(?<key>\??[ \t]*:)?"#).unwrap()
+});
+
+/// The names in `---@generic T, K : Foo`.
+static GENERIC_REGEX: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"@generic[ \t]+(?<names>.*)").unwrap());
+
+/// Types LuaLS knows without a `@class`.
+const BUILTIN_TYPES: &[&str] = &[
+    "any",
+    "boolean",
+    "false",
+    "fun",
+    "function",
+    "integer",
+    "lightuserdata",
+    "nil",
+    "number",
+    "self",
+    "string",
+    "table",
+    "thread",
+    "true",
+    "unknown",
+    "userdata",
+];
+
+/// Modules, classes, and their members discovered across the whole run.
+#[derive(Debug, Default)]
+pub struct Symbols {
+    /// Emitted LDoc module/class names
+    modules: HashSet<String>,
+    /// Per file, the name of the table a module is declared on → the module's name
+    locals: HashMap<PathBuf, HashMap<String, String>>,
+    /// Module name → member name → whether the member is a method
+    members: HashMap<String, HashMap<String, bool>>,
+    /// Documented global functions
+    globals: HashSet<String>,
+    /// Every `@class` in the run, including ones without a table like in stub files
+    classes: HashSet<String>,
+    /// The path of each file as a dotted module name like `pkg.foo`, without a trailing
+    /// `init`, → the class it returns
+    files: Vec<(String, String)>,
+}
+
+impl Symbols {
+    pub fn collect<'a>(files: impl IntoIterator<Item = (&'a SourceFile, &'a [Chunk<'a>])>) -> Self {
+        let mut symbols = Symbols::default();
+
+        for (file, chunks) in files {
+            let source = file.contents.as_bytes();
+            let locals = symbols.locals.entry(file.path.clone()).or_default();
+
+            for chunk in chunks.iter() {
+                for attr in chunk.attrs() {
+                    if let Attribute::Class { ty, .. } = attr {
+                        symbols.classes.insert(ty.clone());
+                    }
+                }
+            }
+
+            for chunk in chunks.iter().filter(|chunk| chunk.is_class()) {
+                let Some(name) = chunk.name() else {
+                    continue;
+                };
+                for attr in chunk.attrs() {
+                    if let Attribute::Class { ty, .. } = attr {
+                        symbols.modules.insert(ty.clone());
+                        locals.insert(name.to_string(), ty.clone());
+                    }
+                }
+            }
+
+            let returned = returned_name(file.tree.root_node(), source);
+            if let Some(class) = returned.and_then(|name| locals.get(name)) {
+                symbols
+                    .files
+                    .push((dotted_module_name(&file.path), class.clone()));
+            }
+
+            for chunk in chunks.iter() {
+                let (table, member, is_method) = match &chunk.decl {
+                    Declaration::Function(Some(table), node) => {
+                        let Some(name) = node.child_by_field_name("name") else {
+                            continue;
+                        };
+                        match name.kind() {
+                            "method_index_expression" => {
+                                let Some(method) = name.child_by_field_name("method") else {
+                                    continue;
+                                };
+                                (table, method.utf8_text(source).unwrap_or_default(), true)
+                            }
+                            "dot_index_expression" => {
+                                let Some(field) = name.child_by_field_name("field") else {
+                                    continue;
+                                };
+                                (table, field.utf8_text(source).unwrap_or_default(), false)
+                            }
+                            _ => {
+                                symbols.globals.insert(table.clone());
+                                continue;
+                            }
+                        }
+                    }
+                    Declaration::Variable(name, _) | Declaration::Target(name, ..) => {
+                        let Some((table, field)) = name.split_once('.') else {
+                            continue;
+                        };
+                        let table = table.to_string();
+                        symbols
+                            .members
+                            .entry(locals.get(&table).unwrap_or(&table).clone())
+                            .or_default()
+                            .insert(field.to_string(), false);
+                        continue;
+                    }
+                    _ => continue,
+                };
+
+                let module = locals.get(table).unwrap_or(table).clone();
+                symbols
+                    .members
+                    .entry(module)
+                    .or_default()
+                    .insert(member.to_string(), is_method);
+            }
+        }
+
+        symbols
+    }
+
+    /// Rewrite `@see` links in `chunks` into LDoc's `module.function`/`module:method` form.
+    /// Anchors like `module#function` are taken as members. URLs are left alone.
+    ///
+    /// References that can't be resolved are left as-is with a warning.
+    pub fn resolve_see(&self, path: &Path, chunks: &mut [Chunk]) {
+        for chunk in chunks.iter_mut() {
+            for attr in chunk.attributes.iter_mut() {
+                let line = attr.line;
+                let Attribute::See { link, .. } = &mut attr.value else {
+                    continue;
+                };
+                if is_url(link) {
+                    continue;
+                }
+                let resolved = match link.split_once('#') {
+                    Some((module, anchor)) => self.resolve(path, &format!("{module}.{anchor}")),
+                    None => self.resolve(path, link),
+                };
+                match resolved {
+                    Some(resolved) => *link = resolved,
+                    None => diag::warn(
+                        path,
+                        line,
+                        diag::Kind::UnresolvedSee,
+                        &format!("unresolved @see reference `{link}`"),
+                    ),
+                }
+            }
+        }
+    }
+
+    /// Rewrite references to known symbols in descriptions, like `` `Foo.bar` ``, into
+    /// LDoc's `@{Foo.bar}` links for `--link-inline-refs`. Code blocks and references
+    /// that can't be resolved are left alone.
+    pub fn link_inline_refs(&self, path: &Path, chunks: &mut [Chunk]) {
+        for chunk in chunks.iter_mut() {
+            let mut in_fence = false;
+            for line in chunk.body.iter_mut() {
+                let text = line.trim_start().trim_start_matches('-').trim_start();
+                if text.starts_with("```") {
+                    in_fence = !in_fence;
+                } else if !in_fence {
+                    *line = self.link_refs(path, line);
+                }
+            }
+
+            for attr in chunk.attrs_mut() {
+                if let Attribute::Param {
+                    desc: Some(desc), ..
+                }
+                | Attribute::Field {
+                    desc: Some(desc), ..
+                }
+                | Attribute::Return {
+                    desc: Some(desc), ..
+                } = attr
+                {
+                    *desc = self.link_refs(path, desc);
+                }
+            }
+        }
+    }
+
+    fn link_refs(&self, path: &Path, text: &str) -> String {
+        INLINE_REF_REGEX
+            .replace_all(text, |captures: &regex::Captures| {
+                // Plain words in backticks are too often just code
+                let link = &captures["ref"];
+                match self.resolve(path, link) {
+                    Some(resolved) if link.contains(['.', ':']) => format!("@{{{resolved}}}"),
+                    _ => captures[0].to_string(),
+                }
+            })
+            .into_owned()
+    }
+
+    /// Add a `@see` to the replacement of `@deprecated` chunks, the first reference in the
+    /// message like `` `NewThing` `` that resolves. Messages without one are left as a
+    /// plain note.
+    pub fn link_deprecated(&self, path: &Path, chunks: &mut [Chunk]) {
+        for chunk in chunks.iter_mut() {
+            let Some(message) = chunk.attrs().find_map(|attr| match attr {
+                Attribute::Deprecated { message } => message.as_deref(),
+                _ => None,
+            }) else {
+                continue;
+            };
+            let Some(resolved) = INLINE_REF_REGEX
+                .captures_iter(message)
+                .find_map(|captures| self.resolve(path, &captures["ref"]))
+            else {
+                continue;
+            };
+            if !chunk
+                .attrs()
+                .any(|attr| matches!(attr, Attribute::See { link, .. } if *link == resolved))
+            {
+                chunk.attributes.push(
+                    Attribute::See {
+                        link: resolved,
+                        desc: None,
+                    }
+                    .into(),
+                );
+            }
+        }
+    }
+
+    /// Add a `@see` to a documented re-export like `M.foo = require("pkg.foo")` pointing at
+    /// the module it requires. Modules from outside the run are left alone, since there's
+    /// nothing to link to.
+    pub fn link_requires(&self, file: &SourceFile, chunks: &mut [Chunk]) {
+        let source = file.contents.as_bytes();
+        for chunk in chunks.iter_mut() {
+            let Declaration::Variable(_, decl) = &chunk.decl else {
+                continue;
+            };
+            let Some(resolved) =
+                required_module(*decl, source).and_then(|module| self.resolve(&file.path, &module))
+            else {
+                continue;
+            };
+            if !chunk
+                .attrs()
+                .any(|attr| matches!(attr, Attribute::See { link, .. } if *link == resolved))
+            {
+                chunk.attributes.push(
+                    Attribute::See {
+                        link: resolved,
+                        desc: None,
+                    }
+                    .into(),
+                );
+            }
+        }
+    }
+
+    /// Write classes from the run in `@param`, `@return`, and `@field` types with their
+    /// module path, so LDoc links them with `--root-module` too. If `warn` is set, other
+    /// names that aren't built in, generic, or a module are warned about for `--check-types`.
+    pub fn link_types(&self, path: &Path, chunks: &mut [Chunk], warn: bool) {
+        // Generic classes can use their parameters anywhere in the file
+        let mut generics = chunks
+            .iter()
+            .flat_map(|chunk| chunk.attrs())
+            .flat_map(|attr| match attr {
+                Attribute::Class { generics, .. } => generics.as_slice(),
+                _ => &[],
+            })
+            .map(|generic| generic_name(generic).to_string())
+            .collect::<HashSet<_>>();
+        let mut warned = HashSet::<(Option<usize>, String)>::new();
+
+        for chunk in chunks.iter_mut() {
+            let local = chunk
+                .body
+                .iter()
+                .filter_map(|line| GENERIC_REGEX.captures(line))
+                .flat_map(|captures| {
+                    captures["names"]
+                        .split(',')
+                        .map(|generic| generic_name(generic).to_string())
+                        .collect::<Vec<_>>()
+                })
+                .filter(|generic| !generic.is_empty())
+                .collect::<Vec<_>>();
+            generics.extend(local.iter().cloned());
+
+            for attr in chunk.attributes.iter_mut() {
+                let line = attr.line;
+                let (tag, ty) = match &mut attr.value {
+                    Attribute::Param { ty, .. } => ("@param", ty),
+                    Attribute::Return { ty, .. } => ("@return", ty),
+                    Attribute::Field { ty, .. } => ("@field", ty),
+                    _ => continue,
+                };
+                *ty = TYPE_NAME_REGEX
+                    .replace_all(ty, |captures: &regex::Captures| {
+                        let Some(name) = captures.name("name").map(|name| name.as_str()) else {
+                            return captures[0].to_string();
+                        };
+                        if captures.name("key").is_some()
+                            || BUILTIN_TYPES.contains(&name)
+                            || generics.contains(name)
+                        {
+                            return captures[0].to_string();
+                        }
+                        if self.classes.contains(name) {
+                            return captures[0].replacen(name, &module_path(name), 1);
+                        }
+                        if warn
+                            && self.resolve(path, name).is_none()
+                            && warned.insert((line, name.to_string()))
+                        {
+                            diag::warn(
+                                path,
+                                line,
+                                diag::Kind::UnknownType,
+                                &format!("{tag} type `{name}` isn't a class or module in the run"),
+                            );
+                        }
+                        captures[0].to_string()
+                    })
+                    .into_owned();
+            }
+
+            // Only for this function
+            for generic in local {
+                generics.remove(&generic);
+            }
+        }
+    }
+
+    fn resolve(&self, path: &Path, link: &str) -> Option<String> {
+        let (head, tail) = match link.split_once(['.', ':']) {
+            Some((head, tail)) => (head, Some(tail)),
+            None => (link, None),
+        };
+
+        let module = if self.modules.contains(head) {
+            head
+        } else if let Some(module) = self.locals.get(path).and_then(|locals| locals.get(head)) {
+            module.as_str()
+        } else if tail.is_none() && self.globals.contains(head) {
+            return Some(head.to_string());
+        } else {
+            return self.resolve_file(link);
+        };
+
+        let Some(tail) = tail else {
+            return Some(module_path(module));
+        };
+
+        let is_method = self.members.get(module)?.get(tail)?;
+        let sep = if *is_method { ':' } else { '.' };
+        Some(format!("{}{sep}{tail}", module_path(module)))
+    }
+
+    /// Resolve a module name like the `pkg.foo` in `require("pkg.foo")` to the class its
+    /// file returns. The file's path only has to end with it, since the root it's required
+    /// relative to isn't known.
+    fn resolve_file(&self, link: &str) -> Option<String> {
+        let suffix = format!(".{link}");
+        self.files
+            .iter()
+            .find(|(path, _)| path == link || path.ends_with(&suffix))
+            .map(|(_, class)| module_path(class))
+    }
+}
@@ -0,0 +1,505 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! A hand-written parser for LuaLS type expressions, used to match `@param`, `@return`,
+//! `@field`, and `@alias` lines when built without the `pcre2` feature.
+//!
+//! It accepts exactly what the recursive PCRE2 patterns in [`crate::attr`] do. Every rule
+//! returns all the positions a match starting at some position can end at, in the order a
+//! backtracking regex would try them, so taking the first end that lets the rest of the
+//! line match picks the same type the regex would.
+
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Range,
+};
+
+use crate::attr::{doc_prefix, TypedLine};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Rule {
+    Type,
+    Union,
+    Args,
+    Returns,
+}
+
+struct Parser<'a> {
+    s: &'a str,
+    memo: HashMap<(Rule, usize), Vec<usize>>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(s: &'a str) -> Self {
+        Self {
+            s,
+            memo: HashMap::new(),
+        }
+    }
+
+    fn at(&self, i: usize, lit: &str) -> bool {
+        self.s[i..].starts_with(lit)
+    }
+
+    /// Skip `[ \t]*`.
+    fn blanks(&self, i: usize) -> usize {
+        self.s.len() - self.s[i..].trim_start_matches([' ', '\t']).len()
+    }
+
+    /// The end of the line `i` is on, before the newline.
+    fn eol(&self, i: usize) -> usize {
+        self.s[i..].find('\n').map_or(self.s.len(), |len| i + len)
+    }
+
+    /// `\w+`, longest first.
+    fn word(&self, i: usize) -> Vec<usize> {
+        let mut ends = self.s[i..]
+            .char_indices()
+            .take_while(|(_, c)| c.is_alphanumeric() || *c == '_')
+            .map(|(offset, c)| i + offset + c.len_utf8())
+            .collect::<Vec<_>>();
+        ends.reverse();
+        ends
+    }
+
+    /// `-?\d[\w.]*`, a number literal like `-1`, `1.5`, or `0x10`. Longest first.
+    fn number(&self, i: usize) -> Vec<usize> {
+        let start = if self.at(i, "-") { i + 1 } else { i };
+        if !self.s[start..].starts_with(|c: char| c.is_ascii_digit()) {
+            return Vec::new();
+        }
+        let mut ends = self.s[start + 1..]
+            .char_indices()
+            .take_while(|(_, c)| c.is_alphanumeric() || *c == '_' || *c == '.')
+            .map(|(offset, c)| start + 1 + offset + c.len_utf8())
+            .collect::<Vec<_>>();
+        ends.insert(0, start + 1);
+        ends.reverse();
+        ends
+    }
+
+    /// `^[ \t]*---[ \t]*{tag}[ \t]+`, returning where the text after it starts.
+    fn after_tag(&self, i: usize, tag: &str) -> Option<usize> {
+        let i = self.blanks(i);
+        if !self.at(i, doc_prefix()) {
+            return None;
+        }
+        let i = self.blanks(i + doc_prefix().len());
+        if !self.at(i, tag) {
+            return None;
+        }
+        let start = self.blanks(i + tag.len());
+        (start > i + tag.len()).then_some(start)
+    }
+
+    fn memoized(
+        &mut self,
+        rule: Rule,
+        i: usize,
+        f: fn(&mut Self, usize) -> Vec<usize>,
+    ) -> Vec<usize> {
+        if let Some(ends) = self.memo.get(&(rule, i)) {
+            return ends.clone();
+        }
+        let mut ends = f(self, i);
+        // Trying the same end again can't match anything new
+        let mut seen = HashSet::new();
+        ends.retain(|end| seen.insert(*end));
+        self.memo.insert((rule, i), ends.clone());
+        ends
+    }
+
+    /// A whole type: `member ([ \t]*\|[ \t]*type)*`.
+    fn ty(&mut self, i: usize) -> Vec<usize> {
+        self.memoized(Rule::Type, i, |this, i| {
+            let mut ends = Vec::new();
+            for end in this.member(i) {
+                ends.extend(this.union(end));
+            }
+            ends
+        })
+    }
+
+    /// The rest of a union after its first member, trying more members first.
+    fn union(&mut self, i: usize) -> Vec<usize> {
+        self.memoized(Rule::Union, i, |this, i| {
+            let mut ends = Vec::new();
+            let bar = this.blanks(i);
+            if this.at(bar, "|") {
+                for end in this.ty(this.blanks(bar + 1)) {
+                    ends.extend(this.union(end));
+                }
+            }
+            ends.push(i);
+            ends
+        })
+    }
+
+    /// One member of a union, optionally parenthesized, with its `[]`s and `?`.
+    fn member(&mut self, i: usize) -> Vec<usize> {
+        let mut ends = Vec::new();
+        for end in self.primary(i) {
+            ends.extend(self.suffix(end));
+        }
+        if self.at(i, "(") {
+            for end in self.ty(i + 1) {
+                if self.at(end, ")") {
+                    ends.extend(self.suffix(end + 1));
+                }
+            }
+        }
+        ends
+    }
+
+    /// `(\[\])*\??`
+    fn suffix(&self, i: usize) -> Vec<usize> {
+        let mut arrays = vec![i];
+        while let Some(&end) = arrays.last().filter(|&&end| self.at(end, "[]")) {
+            arrays.push(end + 2);
+        }
+        arrays
+            .into_iter()
+            .rev()
+            .flat_map(|end| match self.at(end, "?") {
+                true => vec![end + 1, end],
+                false => vec![end],
+            })
+            .collect()
+    }
+
+    fn primary(&mut self, i: usize) -> Vec<usize> {
+        let mut ends = Vec::new();
+        ends.extend(self.braces(i));
+        if self.at(i, "table<") {
+            for key in self.ty(i + "table<".len()) {
+                if self.at(key, ",") {
+                    for value in self.ty(self.blanks(key + 1)) {
+                        if self.at(value, ">") {
+                            ends.push(value + 1);
+                        }
+                    }
+                }
+            }
+        }
+        if self.at(i, "fun(") {
+            ends.extend(self.fun(i + "fun(".len()));
+        }
+        ends.extend(self.number(i));
+        ends.extend(self.word(i));
+        for quote in ['"', '\''] {
+            if self.s[i..].starts_with(quote) {
+                ends.extend(self.s[i + 1..].find(quote).map(|len| i + len + 2));
+            }
+        }
+        ends
+    }
+
+    /// A table literal like `{ a: number }`, with at most one level of nested braces.
+    fn braces(&self, i: usize) -> Option<usize> {
+        if !self.at(i, "{") {
+            return None;
+        }
+        let mut depth = 0;
+        for (offset, c) in self.s[i..].char_indices() {
+            match c {
+                '{' if depth == 2 => return None,
+                '{' => depth += 1,
+                '}' if depth == 1 => return Some(i + offset + 1),
+                '}' => depth -= 1,
+                _ => (),
+            }
+        }
+        None
+    }
+
+    /// The rest of a `fun(` type: its arguments, the `)`, and any returns.
+    fn fun(&mut self, i: usize) -> Vec<usize> {
+        let mut starts = self.arg(i);
+        starts.push(i);
+
+        let mut ends = Vec::new();
+        for start in starts {
+            for end in self.args(start) {
+                let close = self.blanks(end);
+                if self.at(close, ")") {
+                    ends.extend(self.returns(close + 1));
+                    ends.push(close + 1);
+                }
+            }
+        }
+        ends
+    }
+
+    /// `name?: type`
+    fn arg(&mut self, i: usize) -> Vec<usize> {
+        let Some(&name_end) = self.word(i).first() else {
+            return Vec::new();
+        };
+        let colon = if self.at(name_end, "?") {
+            name_end + 1
+        } else {
+            name_end
+        };
+        match self.at(colon, ":") {
+            true => self.ty(self.blanks(colon + 1)),
+            false => Vec::new(),
+        }
+    }
+
+    /// `(,[ \t]*arg)*`
+    fn args(&mut self, i: usize) -> Vec<usize> {
+        self.memoized(Rule::Args, i, |this, i| {
+            let mut ends = Vec::new();
+            if this.at(i, ",") {
+                for end in this.arg(this.blanks(i + 1)) {
+                    ends.extend(this.args(end));
+                }
+            }
+            ends.push(i);
+            ends
+        })
+    }
+
+    /// `: returns` or `: (returns)` after a `fun(...)`.
+    fn returns(&mut self, i: usize) -> Vec<usize> {
+        if !self.at(i, ":") {
+            return Vec::new();
+        }
+        let start = self.blanks(i + 1);
+
+        let mut ends = Vec::new();
+        if self.at(start, "(") {
+            for end in self.return_list(start + 1) {
+                if self.at(end, ")") {
+                    ends.push(end + 1);
+                }
+            }
+        }
+        ends.extend(self.return_list(start));
+        ends
+    }
+
+    fn return_list(&mut self, i: usize) -> Vec<usize> {
+        let mut ends = Vec::new();
+        for end in self.fun_return(i) {
+            ends.extend(self.more_returns(end));
+        }
+        ends
+    }
+
+    /// `(,[ \t]*return)*`
+    fn more_returns(&mut self, i: usize) -> Vec<usize> {
+        self.memoized(Rule::Returns, i, |this, i| {
+            let mut ends = Vec::new();
+            if this.at(i, ",") {
+                for end in this.fun_return(this.blanks(i + 1)) {
+                    ends.extend(this.more_returns(end));
+                }
+            }
+            ends.push(i);
+            ends
+        })
+    }
+
+    /// A return of a `fun`, which can be named like `ok: boolean`.
+    fn fun_return(&mut self, i: usize) -> Vec<usize> {
+        let mut ends = self.arg(i);
+        ends.extend(self.ty(i));
+        ends
+    }
+
+    /// `[ \t]+(?<desc>.*$)` after a type.
+    fn desc(&self, i: usize) -> Option<&'a str> {
+        let start = self.blanks(i);
+        (start > i).then(|| &self.s[start..])
+    }
+
+    /// `name[ \t]+type`, with an optional description.
+    fn named(&mut self, i: usize, vis: Option<&'a str>) -> Option<TypedLine<'a>> {
+        let name_end = *self.word(i).first()?;
+        let start = self.blanks(name_end);
+        if start == name_end {
+            return None;
+        }
+        let end = *self.ty(start).first()?;
+        Some(TypedLine {
+            name: Some(&self.s[i..name_end]),
+            ty: &self.s[start..end],
+            desc: self.desc(end),
+            vis,
+        })
+    }
+
+    /// Match one `@alias` and its `---|` lines starting at `i`, returning where it ends.
+    fn alias(&mut self, i: usize) -> Option<usize> {
+        let start = self.after_tag(i, "@alias")?;
+        let name_end = *self.word(start).first()?;
+
+        // The rest of the line has to be empty or a comment
+        let rest = |this: &Self, end: usize| {
+            let comment = this.blanks(end);
+            if comment > end && (this.at(comment, "#") || this.at(comment, "--")) {
+                Some(this.eol(comment))
+            } else {
+                (this.eol(comment) == comment).then_some(comment)
+            }
+        };
+
+        let ty_start = self.blanks(name_end);
+        let mut end = None;
+        if ty_start > name_end {
+            end = self
+                .ty(ty_start)
+                .into_iter()
+                .find_map(|ty_end| rest(self, ty_end));
+        }
+        let mut end = end.or_else(|| rest(self, name_end))?;
+
+        loop {
+            let line = self.s.len() - self.s[end..].trim_start().len();
+            if line == end || !self.at(line, doc_prefix()) {
+                break;
+            }
+            let bar = self.blanks(line + doc_prefix().len());
+            if !self.at(bar, "|") {
+                break;
+            }
+            // `---|>` and `---|+` mark the default and extra values
+            let bar = match self.at(bar + 1, ">") || self.at(bar + 1, "+") {
+                true => bar + 1,
+                false => bar,
+            };
+            let Some(&ty_end) = self.ty(self.blanks(bar + 1)).first() else {
+                break;
+            };
+            end = match self.blanks(ty_end) {
+                comment if comment > ty_end => self.eol(comment),
+                _ => ty_end,
+            };
+        }
+
+        Some(end)
+    }
+}
+
+pub fn param(line: &str) -> Option<TypedLine<'_>> {
+    let mut parser = Parser::new(line);
+    let start = parser.after_tag(0, "@param")?;
+
+    let mut name_end = match parser.word(start).first() {
+        Some(&end) => end,
+        None if parser.at(start, "...") => start + 3,
+        None => return None,
+    };
+    // Dotted names like `opts.timeout`
+    while parser.at(name_end, ".") {
+        match parser.word(name_end + 1).first() {
+            Some(&end) => name_end = end,
+            None => break,
+        }
+    }
+    // Optional ones like `x?`
+    if parser.at(name_end, "?") {
+        name_end += 1;
+    }
+
+    let ty_start = parser.blanks(name_end);
+    if ty_start == name_end {
+        return None;
+    }
+    let ty_end = *parser.ty(ty_start).first()?;
+    Some(TypedLine {
+        name: Some(&line[start..name_end]),
+        ty: &line[ty_start..ty_end],
+        desc: parser.desc(ty_end),
+        vis: None,
+    })
+}
+
+pub fn ret(line: &str) -> Option<TypedLine<'_>> {
+    let mut parser = Parser::new(line);
+    let start = parser.after_tag(0, "@return")?;
+    let ty_end = *parser.ty(start).first()?;
+
+    let mut ret = TypedLine {
+        name: None,
+        ty: &line[start..ty_end],
+        desc: None,
+        vis: None,
+    };
+
+    // `@return number... counts` is variadic too
+    if parser.at(ty_end, "...") {
+        ret.name = Some(&line[ty_end..ty_end + 3]);
+        let desc = parser.blanks(ty_end + 3);
+        if desc > ty_end + 3 {
+            let desc = match parser.at(desc, "#") {
+                true => parser.blanks(desc + 1),
+                false => desc,
+            };
+            ret.desc = Some(&line[desc..]);
+        }
+        return Some(ret);
+    }
+
+    let next = parser.blanks(ty_end);
+    if parser.at(next, "#") {
+        // `@return boolean # whether it worked` has a comment but no name
+        ret.desc = Some(&line[parser.blanks(next + 1)..]);
+    } else if next == ty_end {
+        return Some(ret);
+    } else if let Some(name_end) = parser
+        .word(next)
+        .first()
+        .copied()
+        .or_else(|| parser.at(next, "...").then_some(next + 3))
+    {
+        ret.name = Some(&line[next..name_end]);
+        let desc = parser.blanks(name_end);
+        if desc > name_end {
+            let desc = match parser.at(desc, "#") {
+                true => parser.blanks(desc + 1),
+                false => desc,
+            };
+            ret.desc = Some(&line[desc..]);
+        }
+    }
+    Some(ret)
+}
+
+pub fn field(line: &str) -> Option<TypedLine<'_>> {
+    let mut parser = Parser::new(line);
+    let start = parser.after_tag(0, "@field")?;
+
+    for vis in ["public", "protected", "private", "package"] {
+        let vis_end = start + vis.len();
+        if parser.at(start, vis) && parser.blanks(vis_end) > vis_end {
+            if let Some(field) = parser.named(parser.blanks(vis_end), Some(&line[start..vis_end])) {
+                return Some(field);
+            }
+        }
+    }
+    // Not a visibility after all, like a field named `private`
+    parser.named(start, None)
+}
+
+/// Find every `@alias` in `source`, including the `---|` lines after it.
+pub fn aliases(source: &str) -> Vec<Range<usize>> {
+    let mut parser = Parser::new(source);
+    let mut ranges = Vec::<Range<usize>>::new();
+
+    let mut line = 0;
+    loop {
+        if ranges.last().is_none_or(|range| range.end <= line) {
+            if let Some(end) = parser.alias(line) {
+                ranges.push(line..end);
+            }
+        }
+        match source[line..].find('\n') {
+            Some(len) => line += len + 1,
+            None => break,
+        }
+    }
+    ranges
+}
@@ -0,0 +1,202 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Command line behavior that isn't about converting a file.
+
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+fn ldoc_gen(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_ldoc_gen"))
+        .args(args)
+        .output()
+        .expect("ldoc_gen runs");
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// Run `ldoc_gen`, expecting it to fail, and return what it printed to stderr.
+fn ldoc_gen_fails(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_ldoc_gen"))
+        .args(args)
+        .output()
+        .expect("ldoc_gen runs");
+    assert!(!output.status.success(), "ldoc_gen {args:?} succeeded");
+    assert!(output.stdout.is_empty(), "{args:?} printed to stdout");
+    String::from_utf8(output.stderr).unwrap()
+}
+
+/// A fresh directory for a test with `files` in it, as (relative path, contents).
+fn project(name: &str, files: &[(&str, &str)]) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR"))
+        .join("cli")
+        .join(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    for (path, contents) in files {
+        let path = dir.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    }
+    dir
+}
+
+#[test]
+fn version_has_the_grammar_crate_version() {
+    let version = ldoc_gen(&["--version"]);
+    let grammar = version
+        .lines()
+        .find(|line| line.starts_with("tree-sitter-lua "))
+        .unwrap_or_else(|| panic!("no tree-sitter-lua version in:\n{version}"));
+    // A crate version like `0.0.19`, then the ABI
+    let crate_version = grammar.split_whitespace().nth(1).unwrap();
+    assert_eq!(crate_version.split('.').count(), 3, "{grammar}");
+    assert!(grammar.contains("grammar ABI"), "{grammar}");
+}
+
+#[test]
+fn output_is_the_same_every_run() {
+    let fixture = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/class_order.lua"
+    );
+    let runs = (0..5)
+        .map(|_| ldoc_gen(&["--path", fixture, "--output-on-stdout", "--no-progress"]))
+        .collect::<Vec<_>>();
+    assert!(!runs[0].is_empty());
+    assert!(runs.iter().all(|run| *run == runs[0]), "{runs:#?}");
+}
+
+#[test]
+fn type_aliases_can_keep_unknown() {
+    let source = "---@param a unknown\n---@param b unknown|string\nfunction f(a, b) end\n";
+    let dir = project(
+        "type_aliases",
+        &[
+            ("a.lua", source),
+            (".ldoc_gen.toml", "[type_aliases]\nunknown = \"unknown\"\n"),
+        ],
+    );
+    let path = dir.join("a.lua");
+    let output = ldoc_gen(&["--path", path.to_str().unwrap(), "--output-on-stdout"]);
+    assert!(output.contains("---@tparam unknown a"), "{output}");
+    assert!(output.contains("---@tparam unknown|string b"), "{output}");
+}
+
+#[test]
+fn output_nested_in_the_source_is_not_read_back() {
+    let dir = project(
+        "nested_output",
+        &[("src/a.lua", "---Does a.\nfunction a() end\n")],
+    );
+    let src = dir.join("src");
+    // Not the same spelling as `--path`, so only comparing canonical paths finds it
+    let out_dir = dir.join("src/../src/docs");
+    for _ in 0..2 {
+        ldoc_gen(&[
+            "--path",
+            src.to_str().unwrap(),
+            "--out-dir",
+            out_dir.to_str().unwrap(),
+            "--no-progress",
+        ]);
+    }
+
+    let written = files_in(&dir.join("src/docs"));
+    assert_eq!(written, [Path::new(".ldoc_gen/a.lua")], "{written:?}");
+}
+
+/// Every file under `dir`, relative to it.
+fn files_in(dir: &Path) -> Vec<PathBuf> {
+    let mut files = walkdir::WalkDir::new(dir)
+        .into_iter()
+        .map(|entry| entry.unwrap())
+        .filter(|entry| entry.file_type().is_file())
+        .map(|entry| entry.path().strip_prefix(dir).unwrap().to_path_buf())
+        .collect::<Vec<_>>();
+    files.sort();
+    files
+}
+
+#[test]
+fn integer_as() {
+    let source = "---@class Foo\n---@field n integer\nlocal Foo = {}\n\n\
+                  ---@param a integer?\n---@param b integer|string\n---@return integer\n\
+                  function Foo.f(a, b) end\n\nreturn Foo\n";
+    let dir = project("integer_as", &[("a.lua", source)]);
+    let path = dir.join("a.lua");
+    for (flag, name) in [
+        (None, "integer"),
+        (Some("int"), "int"),
+        (Some("number"), "number"),
+        (Some("integer"), "integer"),
+    ] {
+        let mut args = vec!["--path", path.to_str().unwrap(), "--output-on-stdout"];
+        if let Some(flag) = flag {
+            args.extend(["--integer-as", flag]);
+        }
+        let output = ldoc_gen(&args);
+        for line in [
+            format!("---@tfield {name} n"),
+            format!("---@tparam[opt] {name} a"),
+            format!("---@tparam {name}|string b"),
+            format!("---@treturn {name}"),
+        ] {
+            assert!(
+                output.contains(&line),
+                "{flag:?}: no `{line}` in:\n{output}"
+            );
+        }
+    }
+}
+
+#[test]
+fn watch_converts_changed_files_again() {
+    let dir = project("watch", &[("a.lua", "---Does a.\nfunction a() end\n")]);
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ldoc_gen"))
+        .args(["--path", dir.to_str().unwrap(), "--out-dir"])
+        .arg(&dir)
+        .args(["--no-progress", "--watch"])
+        .spawn()
+        .expect("ldoc_gen runs");
+    let out_path = dir.join(".ldoc_gen/a.lua");
+    let wait_for = |text: &str| {
+        for _ in 0..100 {
+            if std::fs::read_to_string(&out_path).is_ok_and(|out| out.contains(text)) {
+                return true;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(50));
+        }
+        false
+    };
+
+    let first = wait_for("---Does a.");
+    // Some file systems only keep modification times to the second
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    std::fs::write(dir.join("a.lua"), "---Does a.\nfunction a(x, y) end\n").unwrap();
+    let second = wait_for("function a(x, y) end");
+    let _ = child.kill();
+    let _ = child.wait();
+    assert!(first, "the first run didn't write {}", out_path.display());
+    assert!(second, "the change wasn't converted");
+}
+
+#[test]
+fn nothing_else_prints_into_output_on_stdout() {
+    let fixture = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/class_order.lua"
+    );
+    for extra in [
+        &["--report"][..],
+        &["--stats"],
+        &["--error-stream", "stdout"],
+    ] {
+        let mut args = vec!["--path", fixture, "--output-on-stdout"];
+        args.extend(extra);
+        let stderr = ldoc_gen_fails(&args);
+        assert!(stderr.contains("--output-on-stdout"), "{extra:?}: {stderr}");
+    }
+}
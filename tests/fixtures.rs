@@ -0,0 +1,124 @@
+// This Source Code Form is subject to the terms of the Mozilla Public
+// License, v. 2.0. If a copy of the MPL was not distributed with this
+// file, You can obtain one at https://mozilla.org/MPL/2.0/.
+
+//! Runs `ldoc_gen` on each `tests/fixtures/<name>.lua` and compares what it writes with
+//! `<name>.expected.lua`, or `<name>.expected.md` for fixtures converted with
+//! `--format markdown`. Extra arguments for a fixture go in `<name>.args`, separated by
+//! whitespace. Each line of an optional `<name>.warnings` has to be in what it prints to
+//! stderr.
+//!
+//! Set `UPDATE_FIXTURES=1` to write the current output as the expected output instead.
+
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+fn fixtures_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures")
+}
+
+/// The `<name>.expected.*` file for a fixture.
+fn expected_path(name: &str) -> PathBuf {
+    let md = fixtures_dir().join(format!("{name}.expected.md"));
+    if md.exists() {
+        md
+    } else {
+        fixtures_dir().join(format!("{name}.expected.lua"))
+    }
+}
+
+/// Run `ldoc_gen` on a fixture, returning what it wrote and what it printed to stderr.
+fn run(name: &str) -> (String, String) {
+    let dir = fixtures_dir();
+    let out_dir = Path::new(env!("CARGO_TARGET_TMPDIR"))
+        .join("fixtures")
+        .join(name);
+    let _ = std::fs::remove_dir_all(&out_dir);
+
+    let args = std::fs::read_to_string(dir.join(format!("{name}.args"))).unwrap_or_default();
+    let output = Command::new(env!("CARGO_BIN_EXE_ldoc_gen"))
+        .arg("--path")
+        .arg(dir.join(format!("{name}.lua")))
+        .arg("--out-dir")
+        .arg(&out_dir)
+        .arg("--no-progress")
+        .args(args.split_whitespace())
+        .current_dir(&dir)
+        .output()
+        .expect("ldoc_gen runs");
+    assert!(
+        output.status.success(),
+        "ldoc_gen failed on {name}: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let ext = match expected_path(name).extension() {
+        Some(ext) if ext == "md" => "md",
+        _ => "lua",
+    };
+    let written = out_dir.join(".ldoc_gen").join(format!("{name}.{ext}"));
+    (
+        std::fs::read_to_string(written).unwrap_or_default(),
+        String::from_utf8_lossy(&output.stderr).into_owned(),
+    )
+}
+
+#[test]
+fn fixtures() {
+    let mut names = std::fs::read_dir(fixtures_dir())
+        .unwrap()
+        .filter_map(|entry| {
+            let file_name = entry.ok()?.file_name().into_string().ok()?;
+            let name = file_name.strip_suffix(".lua")?;
+            (!name.contains(".expected")).then(|| name.to_string())
+        })
+        .collect::<Vec<_>>();
+    names.sort();
+    assert!(!names.is_empty(), "no fixtures found");
+
+    let update = std::env::var_os("UPDATE_FIXTURES").is_some();
+    let mut failed = Vec::new();
+    for name in names {
+        let (actual, _) = run(&name);
+        let expected_path = expected_path(&name);
+        if update {
+            std::fs::write(&expected_path, &actual).unwrap();
+            continue;
+        }
+        let expected = std::fs::read_to_string(&expected_path).unwrap_or_default();
+        if actual != expected {
+            eprintln!("--- {name}: expected\n{expected}\n--- {name}: got\n{actual}");
+            failed.push(name);
+        }
+    }
+    assert!(
+        failed.is_empty(),
+        "fixtures with different output: {failed:?}"
+    );
+}
+
+/// Fixtures whose warnings matter, with the text each one has to print.
+#[test]
+fn fixture_warnings() {
+    let mut checked = 0;
+    for (name, warning) in std::fs::read_dir(fixtures_dir())
+        .unwrap()
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let name = path.file_name()?.to_str()?.strip_suffix(".warnings")?;
+            Some((name.to_string(), std::fs::read_to_string(&path).ok()?))
+        })
+    {
+        let (_, stderr) = run(&name);
+        for line in warning.lines().filter(|line| !line.trim().is_empty()) {
+            assert!(
+                stderr.contains(line.trim()),
+                "{name} didn't warn `{line}`, it printed:\n{stderr}"
+            );
+        }
+        checked += 1;
+    }
+    assert!(checked > 0, "no .warnings fixtures found");
+}
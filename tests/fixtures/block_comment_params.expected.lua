@@ -0,0 +1,5 @@
+--- Add two numbers.
+---@tparam number a The first
+---@tparam number b The second
+---@treturn number
+function M.add(a, b) end
@@ -0,0 +1,11 @@
+local M = {}
+
+--[[--
+Add two numbers.
+@param a number The first
+@param b number The second
+@return number
+]]
+function M.add(a, b) end
+
+return M
@@ -0,0 +1,3 @@
+---Takes an A.
+---@tparam number|boolean|string a
+function M.take(a) end
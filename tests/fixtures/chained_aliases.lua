@@ -0,0 +1,10 @@
+---@alias B number|boolean
+---@alias A B|string
+
+local M = {}
+
+---Takes an A.
+---@param a A
+function M.take(a) end
+
+return M
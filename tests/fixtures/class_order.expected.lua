@@ -0,0 +1,31 @@
+---
+---@module Zebra
+local Zebra = {}
+
+---Stripes.
+function Zebra:stripes() end
+
+---
+---@module Apple
+local Apple = {}
+
+---Peel.
+function Apple:peel() end
+
+---Eat.
+function Apple:eat() end
+
+---
+---@module Mango
+local Mango = {}
+
+---Ripe?
+---@treturn boolean
+function Mango:ripe() end
+
+---A free function.
+---@local
+local function helper() end
+
+---Another free function.
+function global_fn() end
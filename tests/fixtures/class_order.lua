@@ -0,0 +1,29 @@
+---@class Zebra
+local Zebra = {}
+
+---@class Apple
+local Apple = {}
+
+---@class Mango
+local Mango = {}
+
+---Stripes.
+function Zebra:stripes() end
+
+---A free function.
+local function helper() end
+
+---Peel.
+function Apple:peel() end
+
+---Ripe?
+---@return boolean
+function Mango:ripe() end
+
+---Another free function.
+function global_fn() end
+
+---Eat.
+function Apple:eat() end
+
+return helper, global_fn
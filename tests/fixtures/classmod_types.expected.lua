@@ -0,0 +1,15 @@
+---@classmod Point
+---@tfield number x
+local Point = {}
+
+---Move by a vector.
+---@tparam Vec v
+function Point:move(v) end
+
+---@type Vec
+---@tfield number dx
+local Vec = {}
+
+---The length.
+---@treturn number
+function Vec:len() end
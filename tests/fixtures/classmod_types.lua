@@ -0,0 +1,19 @@
+---@class Point
+---@classmod
+---@field x number
+local Point = {}
+
+---@class Vec
+---@classmod
+---@field dx number
+local Vec = {}
+
+---The length.
+---@return number
+function Vec:len() end
+
+---Move by a vector.
+---@param v Vec
+function Point:move(v) end
+
+return Point
@@ -0,0 +1,3 @@
+---Takes a C.
+---@tparam C|number|string c
+function M.take(c) end
@@ -0,0 +1,10 @@
+---@alias C D|string
+---@alias D C|number
+
+local M = {}
+
+---Takes a C.
+---@param c C
+function M.take(c) end
+
+return M
@@ -0,0 +1,11 @@
+---A thing.
+---
+---Things are made here.
+---
+---@module Thing
+---@tfield string name The name
+local Thing = {}
+
+---Make one.
+---@treturn Thing
+function Thing.new() end
@@ -0,0 +1,14 @@
+--!A thing.
+--!@class Thing
+--!@field name string The name
+
+local helper = 5
+
+--!Things are made here.
+local Thing = {}
+
+--!Make one.
+--!@return Thing
+function Thing.new() end
+
+return Thing
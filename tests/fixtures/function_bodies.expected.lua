@@ -0,0 +1,18 @@
+---A one-liner.
+---@tparam string x
+function M.id(x) end
+
+---The body repeats the parameters.
+---@tparam number x
+---@tparam number y
+function M.add(x, y) end
+
+---The name used as a default shows up in the body.
+---@tparam[opt] string name
+function M.greet(name) end
+
+---Has a function inside.
+function M.outer() end
+
+---Assigned from a function expression.
+M.expr = function(a) end
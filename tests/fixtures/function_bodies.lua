@@ -0,0 +1,29 @@
+local M = {}
+
+---A one-liner.
+---@param x string
+function M.id(x) return x end
+
+---The body repeats the parameters.
+---@param x number
+---@param y number
+function M.add(x, y)
+    return M.add(x, y)
+end
+
+---The name used as a default shows up in the body.
+---@param name string?
+function M.greet(name) name = name or "name" return name end
+
+---Has a function inside.
+function M.outer()
+    local function inner()
+        return function() end
+    end
+    return inner
+end
+
+---Assigned from a function expression.
+M.expr = function(a) return (a) end
+
+return M
@@ -0,0 +1,20 @@
+---Doc.
+---@tparam[opt] table a First (Type: `table<string,fun(a:number):boolean>`.)
+---@tparam table b Second (Type: `table<fun(a:number),string>`.)
+---@tparam table c Third (Type: `table<string,number>[]`.)
+---@tparam table d Fourth (Type: `table<string,number>`.)
+---@tparam {function,...} e Fifth (Type: `(fun(x: number): string)[]`.)
+---@tparam function|{string,...} f Sixth (Type: `fun(x: number)`.)
+---@tparam[opt] {function,...} g Seventh (Type: `(fun(x: number): boolean)[]`.)
+function M.f(a, b, c, d, e, f, g) end
+
+---Ret.
+---@treturn table|nil Type: `table<string,fun(a:number):boolean>`.
+---@treturn table Type: `table<fun(a:number),string>`.
+function M.g() end
+
+---
+---@module Obj
+---@tfield table|nil a First (Type: `table<string,fun(a:number):boolean>`.)
+---@tfield table b Second (Type: `table<fun(a:number),string>`.)
+local Obj = {}
@@ -0,0 +1,22 @@
+local M = {}
+---Doc.
+---@param a table<string, fun(a: number): boolean>|nil First
+---@param b table<fun(a: number), string> Second
+---@param c table<string, number>[] Third
+---@param d table<string, number> Fourth
+---@param e (fun(x: number): string)[] Fifth
+---@param f fun(x: number)|string[] Sixth
+---@param g (fun(x: number): boolean)[]|nil Seventh
+function M.f(a, b, c, d, e, f, g) end
+
+---@class Obj
+---@field a table<string, fun(a: number): boolean>|nil First
+---@field b table<fun(a: number), string> Second
+local Obj = {}
+
+---Ret.
+---@return table<string, fun(a: number): boolean>|nil
+---@return table<fun(a: number), string>
+function M.g() end
+
+return M
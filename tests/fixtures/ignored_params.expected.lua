@@ -0,0 +1,9 @@
+---Only uses the third argument.
+---@tparam any _ first
+---@tparam any _ second
+---@tparam number x The one that matters
+function M.third(_, _, x) end
+
+---Documents only the real one.
+---@tparam number x
+function M.skip(_, x) end
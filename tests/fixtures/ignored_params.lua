@@ -0,0 +1,13 @@
+local M = {}
+
+---Only uses the third argument.
+---@param _ any first
+---@param _ any second
+---@param x number The one that matters
+function M.third(_, _, x) end
+
+---Documents only the real one.
+---@param x number
+function M.skip(_, x) end
+
+return M
@@ -0,0 +1,8 @@
+---Add two numbers.
+---@tparam number a
+---@tparam number b
+function M.add(a, b) end
+
+---Documents the next one.
+---@tparam number x
+function M.neg(x) end
@@ -0,0 +1,20 @@
+local M = {}
+
+---Add two numbers.
+-- local old = add(a, b)
+--[[ print(a)
+print(b) ]]
+---@param a number
+---@param b number
+function M.add(a, b) end
+
+---Separated from its function by a blank line.
+
+function M.sub(a, b) end
+
+---Documents the next one.
+---@param x number
+-- print(x)
+function M.neg(x) end
+
+return M
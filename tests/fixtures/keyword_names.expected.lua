@@ -0,0 +1,15 @@
+---
+---@module Foo
+---@tfield string type The kind of foo
+local Foo = {}
+
+---Ends it
+---@tparam Foo self
+---@tparam number a
+---@function end
+Foo["end"] = function(self, a) end
+
+---Make a foo.
+---@tparam string type The kind of foo
+---@treturn Foo
+function Foo.new(type) end
@@ -0,0 +1,11 @@
+---@class Foo
+---@field end fun(self: Foo, a: number) Ends it
+---@field type string The kind of foo
+local Foo = {}
+
+---Make a foo.
+---@param type string The kind of foo
+---@return Foo
+function Foo.new(type) end
+
+return Foo
@@ -0,0 +1,16 @@
+---Only for this file.
+---@local
+---@tparam number x
+function M.helper(x) end
+
+---The public one.
+---@tparam number x
+---@treturn number
+function M.clamped(x) end
+
+---Clamp a number.
+---@tparam number x The number
+---@tparam number lo The lowest it can be
+---@treturn number
+---@local
+local function clamp(x, lo) end
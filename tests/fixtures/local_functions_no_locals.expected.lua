@@ -0,0 +1,4 @@
+---The public one.
+---@tparam number x
+---@treturn number
+function M.clamped(x) end
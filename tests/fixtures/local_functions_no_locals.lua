@@ -0,0 +1,23 @@
+local M = {}
+
+---Clamp a number.
+---@param x number The number
+---@param lo number The lowest it can be
+---@return number
+local function clamp(x, lo)
+    return math.max(x, lo)
+end
+
+---Only for this file.
+---@private
+---@param x number
+function M.helper(x) end
+
+---The public one.
+---@param x number
+---@return number
+function M.clamped(x)
+    return clamp(x, 0)
+end
+
+return M
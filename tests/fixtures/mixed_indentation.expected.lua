@@ -0,0 +1,12 @@
+---Mixed indentation.
+---
+--- A tab-indented note.
+---  Two spaces.
+---
+---    if x then
+---    	return x
+---    end
+---@tparam number a The first
+---@tparam number b The second
+---@treturn number
+function M.add(a, b) end
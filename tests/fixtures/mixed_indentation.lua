@@ -0,0 +1,18 @@
+local M = {}
+
+---Mixed indentation.
+---
+---	A tab-indented note.
+---  Two spaces.
+---
+---```lua
+---if x then
+---	return x
+---end
+---```
+---	@param a number The first
+---  @param b number The second
+--- @return number
+function M.add(a, b) end
+
+return M
@@ -0,0 +1,10 @@
+---Open a file.
+---@tparam string path
+---@treturn boolean ok
+---@treturn string|nil err
+function M.open(path) end
+
+---Read a file.
+---@tparam string path
+---@treturn string|nil contents or nil if it couldn't be read
+function M.read(path) end
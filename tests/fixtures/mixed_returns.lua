@@ -0,0 +1,15 @@
+local M = {}
+
+---Open a file.
+---@param path string
+---@return boolean ok
+---@return string? err
+function M.open(path) end
+
+---Read a file.
+---@param path string
+---@return string? contents
+---@return # or nil if it couldn't be read
+function M.read(path) end
+
+return M
@@ -0,0 +1,10 @@
+---Helpers for a and b.
+---
+---Nothing here keeps state.
+
+---Does a.
+function M.a() end
+
+---Does b.
+---@tparam number x
+function M.b(x) end
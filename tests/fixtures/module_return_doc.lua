@@ -0,0 +1,13 @@
+local M = {}
+
+---Does a.
+function M.a() end
+
+---Does b.
+---@param x number
+function M.b(x) end
+
+---Helpers for a and b.
+---
+---Nothing here keeps state.
+return M
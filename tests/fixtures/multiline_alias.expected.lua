@@ -0,0 +1,4 @@
+---Open a file.
+---@tparam[opt] string|number mode (`"r"`: Read only, `'w'`: Write, truncating the file, `"a+"`: Append, and read, `nil`, `number`: A file descriptor)
+---@treturn boolean
+function M.open(mode) end
@@ -0,0 +1,16 @@
+local M = {}
+
+---How to open a file.
+---@alias Mode
+---| "r" # Read only
+---|   'w'   # Write, truncating the file
+---| "a+" # Append, and read
+---| nil
+---|> number # A file descriptor
+
+---Open a file.
+---@param mode Mode
+---@return boolean
+function M.open(mode) end
+
+return M
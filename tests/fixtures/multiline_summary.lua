@@ -0,0 +1,18 @@
+local M = {}
+
+--- Does a thing
+--- across two lines.
+--- More detail here.
+function M.a() end
+
+--- One line summary. Not split
+--- Second line of description.
+function M.b() end
+
+--- No period here
+--- and here
+---
+--- Description.
+function M.c() end
+
+return M
@@ -0,0 +1,23 @@
+---Both from a call.
+M.a = M.pair()
+
+---Both from a call.
+M.b = M.pair()
+
+---Only one value.
+M.c = 1
+
+---Only one value.
+M.d = nil
+
+---The first and second results.
+local first = M.pair()
+
+---The first and second results.
+local second
+
+---Two values.
+local x = 1
+
+---Two values.
+local y = 2
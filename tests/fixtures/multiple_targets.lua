@@ -0,0 +1,15 @@
+local M = {}
+
+---The first and second results.
+local first, second = M.pair()
+
+---Both from a call.
+M.a, M.b = M.pair()
+
+---Only one value.
+M.c, M.d = 1
+
+---Two values.
+local x, y = 1, 2
+
+return M
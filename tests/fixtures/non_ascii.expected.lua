@@ -0,0 +1,15 @@
+---Grüße an alle – naïve café.
+---@tparam string naïve Der Wert, z. B. „ä“
+---@treturn string ü
+function M.ü(naïve) end
+
+---Setzt die Größe. 日本語のコメント。
+---@tparam string größe One of: `klein`, `groß`.
+function M.setze(größe) end
+
+---Mit Beispiel, optional?
+---
+---@usage
+--- M.beispiel("é")
+---@tparam[opt] string wert Ein „Wert“
+function M.beispiel(wert) end
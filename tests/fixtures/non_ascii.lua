@@ -0,0 +1,23 @@
+local M = {}
+
+---Grüße an alle – naïve café.
+---@param naïve string Der Wert, z. B. „ä“
+---@return string ü
+function M.ü(naïve) end
+
+---@alias Größe "klein"|"groß"
+
+---Setzt die Größe. 日本語のコメント。
+---@param größe Größe
+function M.setze(größe) end
+
+---Mit Beispiel, optional?
+---@param wert string? Ein „Wert“
+---
+--- ### Example
+---```lua
+---M.beispiel("é")
+---```
+function M.beispiel(wert) end
+
+return M
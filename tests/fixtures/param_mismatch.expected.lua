@@ -0,0 +1,4 @@
+---Add two numbers.
+---@tparam number a
+---@tparam number c
+function M.add(a, b) end
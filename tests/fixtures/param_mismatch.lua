@@ -0,0 +1,8 @@
+local M = {}
+
+---Add two numbers.
+---@param a number
+---@param c number
+function M.add(a, b) end
+
+return M
@@ -0,0 +1,7 @@
+---Summary.
+---
+---After a ruler.
+--- A note that belongs to the description.
+--- if you pass nil, nothing happens.
+---@tparam number x
+function M.f(x) end
@@ -0,0 +1,18 @@
+local M = {}
+
+--------------------------------
+-- Banner above the block
+--------------------------------
+---Summary.
+----
+---After a ruler.
+-- A note that belongs to the description.
+-- print("debug")
+-- TODO fix this
+-- local x = 5
+-- if you pass nil, nothing happens.
+---@param x number
+-- trailing note that isn't in the block
+function M.f(x) end
+
+return M
@@ -0,0 +1,16 @@
+---The helpers.
+---@see util
+M.util = require("util")
+
+---A JSON library from outside the run.
+M.json = require("dkjson")
+
+---Helpers.
+---
+---@module util
+local util = {}
+
+---Trim a string.
+---@tparam string s
+---@treturn string
+function util.trim(s) end
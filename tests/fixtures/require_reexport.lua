@@ -0,0 +1,18 @@
+---Helpers.
+---@class util
+local util = {}
+
+---Trim a string.
+---@param s string
+---@return string
+function util.trim(s) end
+
+local M = {}
+
+---The helpers.
+M.util = require("util")
+
+---A JSON library from outside the run.
+M.json = require("dkjson")
+
+return M
@@ -0,0 +1,11 @@
+---
+---@module M
+local M = {}
+
+---Does a.
+---@see foo#bar
+function M.a() end
+
+---Does b, like `M.a`.
+---@see M.a
+function M.b() end
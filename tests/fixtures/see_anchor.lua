@@ -0,0 +1,14 @@
+-- `foo#bar` isn't a module in the run, so it's kept as a `@see` with an "unresolved" warning.
+
+---@class M
+local M = {}
+
+---Does a.
+---@see foo#bar
+function M.a() end
+
+---Does b, like `M.a`.
+---@see M#a
+function M.b() end
+
+return M
@@ -0,0 +1,9 @@
+---Does a.
+---
+---See also: [https://x/y#z](https://x/y#z)
+function M.a() end
+
+---Does b.
+---
+---See also: [The docs for b](https://example.com/docs#b)
+function M.b() end
@@ -0,0 +1,13 @@
+-- LDoc can't link to URLs, so a `@see` with one becomes a "See also" link in the description.
+
+local M = {}
+
+---Does a.
+---@see https://x/y#z
+function M.a() end
+
+---Does b.
+---@see https://example.com/docs#b The docs for b
+function M.b() end
+
+return M
@@ -0,0 +1,4 @@
+---Runs the script.
+---@tparam {string,...} args
+---@local
+local function main(args) end
@@ -0,0 +1,8 @@
+#!/usr/bin/env lua
+---Runs the script.
+---@param args string[]
+local function main(args)
+    print(args)
+end
+
+main(arg)
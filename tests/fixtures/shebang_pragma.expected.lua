@@ -0,0 +1,3 @@
+---Runs the script.
+---@local
+local function main() end
@@ -0,0 +1,8 @@
+#!/usr/bin/env lua
+-- luacheck: globals vim
+
+---Runs the script.
+local function main()
+end
+
+main()
@@ -0,0 +1,7 @@
+---Does the thing.
+---@tparam number x
+---@function do-thing
+M["do-thing"] = function(x) end
+
+---An identifier key.
+M.plain = function() end
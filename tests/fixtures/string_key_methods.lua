@@ -0,0 +1,10 @@
+local M = {}
+
+---Does the thing.
+---@param x number
+M["do-thing"] = function(x) end
+
+---An identifier key.
+M["plain"] = function() end
+
+return M
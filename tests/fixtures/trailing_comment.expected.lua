@@ -0,0 +1,4 @@
+---Does a.
+function M.a() end
+
+---A note at the end of the file.
@@ -0,0 +1,8 @@
+local M = {}
+
+---Does a.
+function M.a() end
+
+return M
+
+---A note at the end of the file.
@@ -0,0 +1,7 @@
+---Widgets you can draw.
+---
+---@module Widget
+local Widget = {}
+
+---Draw it.
+function Widget:draw() end
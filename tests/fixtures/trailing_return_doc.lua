@@ -0,0 +1,8 @@
+---@class Widget
+local Widget = {}
+
+---Draw it.
+function Widget:draw() end
+
+---Widgets you can draw.
+return Widget
@@ -0,0 +1,3 @@
+---Close a file.
+---@tparam file f
+function M.close(f) end
@@ -0,0 +1,8 @@
+local M = {}
+
+---Close a file.
+---@return
+---@param f file*
+function M.close(f) end
+
+return M
@@ -0,0 +1,19 @@
+---Set the mode.
+---@tparam string mode The mode; `fast`: Quick but rough; `slow`: Careful (Known values: `fast`, `slow`, `off`.)
+---@tparam integer level
+---@treturn boolean|nil ok
+function M.set(mode, level) end
+
+---Scale it.
+---@tparam number|string by How much; `auto`: Pick for me (Known values: `auto`.)
+function M.scale(by) end
+
+---Body text.
+---
+---| not a value here
+function M.other() end
+
+---
+---@module Thing
+---@tfield string kind `a`: The first (Known values: `a`, `b`.)
+local Thing = {}
@@ -0,0 +1,28 @@
+local M = {}
+
+---Set the mode.
+---@param mode string The mode
+---| "fast" # Quick but rough
+---|> "slow" # Careful
+---| "off"
+---@param level integer
+---@return boolean ok
+---| nil
+function M.set(mode, level) end
+
+---@class Thing
+---@field kind string
+---| "a" # The first
+---| "b"
+local Thing = {}
+
+---Scale it.
+---@param by number How much
+---| "auto" # Pick for me
+function M.scale(by) end
+
+---Body text.
+---| not a value here
+function M.other() end
+
+return M
@@ -0,0 +1,5 @@
+---Format and print.
+---@tparam string fmt The format
+---@tparam string sep The separator
+---@tparam any ... The values
+function M.printf(fmt, sep, ...) end
@@ -0,0 +1,9 @@
+local M = {}
+
+---Format and print.
+---@param fmt string The format
+---@param sep string The separator
+---@param ... any The values
+function M.printf(fmt, sep, ...) end
+
+return M
@@ -0,0 +1,14 @@
+---Count each of the values.
+---@tparam string sep The separator
+---@tparam string ... The values
+---@treturn number ... counts
+function M.count(sep, ...) end
+
+---Pass the values through.
+---@tparam any ...
+---@treturn any ...
+function M.id(...) end
+
+---A comment instead of a name.
+---@treturn string ... The names
+function M.names() end
@@ -0,0 +1,18 @@
+local M = {}
+
+---Count each of the values.
+---@param sep string The separator
+---@param ... string The values
+---@return number ... counts
+function M.count(sep, ...) end
+
+---Pass the values through.
+---@param ... any
+---@return any ...
+function M.id(...) end
+
+---A comment instead of a name.
+---@return string ... # The names
+function M.names() end
+
+return M